@@ -1,6 +1,6 @@
 // #![feature(trace_macros)]
 use chrono::{NaiveDateTime, Utc};
-use ormx::{Insert, Table};
+use ormx::{Insert, Schema, Table, TableMeta};
 use sqlx::PgPool;
 
 // trace_macros!(true);
@@ -19,6 +19,22 @@ async fn main() -> anyhow::Result<()> {
 
     let db = PgPool::connect(&dotenv::var("DATABASE_URL")?).await?;
 
+    log::info!("create the table, if it doesn't exist yet");
+    println!("{}", User::create_table_sql());
+    println!("{:?}", User::create_indexes_sql());
+    User::create_table(&mut *db.acquire().await?).await?;
+
+    log::info!("check that the table in the database matches the struct");
+    println!("{}", ormx::verify_schema::<User>(&db).await?);
+
+    log::info!("generate the migration bringing the table up to date, if it is outdated");
+    // use `migration.write("migrations", "sync")` to write it into `migrations/`
+    let migration = ormx::alter_sync::<User>(&db).await?;
+    println!("{:#?}", migration);
+
+    log::info!("inspect the table at runtime");
+    println!("{}: {:?}", User::TABLE, User::EMAIL.meta());
+
     log::info!("insert a new row into the database");
     let mut new = InsertUser {
         first_name: "Moritz".to_owned(),
@@ -30,6 +46,10 @@ async fn main() -> anyhow::Result<()> {
     .insert(&mut *db.acquire().await?)
     .await?;
 
+    log::info!("query all users which are not disabled");
+    let active = User::by_disabled(&db, None).await?;
+    println!("{:?}", active);
+
     log::info!("update a single field");
     new.set_last_login(&db, Some(Utc::now().naive_utc()))
         .await?;
@@ -53,33 +73,72 @@ async fn main() -> anyhow::Result<()> {
     new.reload(&db).await?;
 
     log::info!("use the improved query macro for searching users");
-    let search_result = query2::query_users(&db, Some("NewFirstName"), None).await?;
+    let search_result =
+        query2::query_users(&db, Some("NewFirstName"), query2::Order::Newest, None).await?;
     println!("{:?}", search_result);
 
+    log::info!("list all users, ordered by their first name");
+    let all_users = query2::query_users(&db, None, query2::Order::FirstName, Some(10)).await?;
+    println!("{:?}", all_users);
+
+    log::info!("query multiple users by their ids");
+    let by_ids = query2::users_by_ids(&db, &[new.user_id]).await?;
+    println!("{:?}", by_ids);
+
+    log::info!("count disabled users and list the names of all users");
+    println!("{}", query2::count_users(&db, Some(true)).await?);
+    println!("{:?}", query2::user_names(&db, None).await?);
+
+    log::info!("enable the user again");
+    query2::enable_users(&db, Some(&[new.user_id])).await?;
+
     log::info!("delete the user from the database");
     new.delete(&db).await?;
 
     Ok(())
 }
 
+/// A registered user, who may log in using their email address.
 #[derive(Debug, ormx::Table)]
-#[ormx(table = "users", id = user_id, insertable)]
+// generate `User::create_table_sql()` and `User::create_table(db)` using `ddl`
+#[ormx(table = "users", id = user_id, insertable, ddl)]
+// create an index on `last_name, first_name`, named `users_last_name_first_name_idx`
+#[ormx(index(columns = (last_name, first_name)))]
 struct User {
     // map this field to the column "id"
     #[ormx(column = "id")]
     #[ormx(get_one = get_by_user_id)]
+    // `GENERATED BY DEFAULT AS IDENTITY`, use `always` to prevent writing ids explicitly
+    #[ormx(identity = by_default)]
     user_id: i32,
+    #[ormx(sql_type = "VARCHAR(128)")]
     first_name: String,
+    // generate `User::by_last_name(&str, i64) -> Result<Vec<Self>>`, ordered by the first name
+    // override the type inferred from `String` (`TEXT`)
+    #[ormx(get_many(&str, order_by = "first_name", limit))]
+    #[ormx(sql_type = "VARCHAR(128)")]
     last_name: String,
     // generate `User::by_email(&str) -> Result<Option<Self>>`
+    // doc comments become comments on the columns in `CREATE TABLE users`
+    /// The address used to log in, which is unique among all users.
     #[ormx(get_optional(&str))]
+    #[ormx(sql_type = "VARCHAR(128)", unique)]
     email: String,
     #[ormx(custom_type)]
     role: Role,
+    // generate `User::by_disabled(Option<&str>) -> Result<Vec<Self>>`, which also matches NULL
+    #[ormx(get_many(Option<&str>))]
     disabled: Option<String>,
     // don't include this field into `InsertUser` since it has a default value
     // generate `User::set_last_login(Option<NaiveDateTime>) -> Result<()>`
-    #[ormx(default, set)]
+    // generate `User::by_last_login_between(NaiveDateTime, NaiveDateTime) -> Result<Vec<Self>>`
+    // if the database still contains the column "last_seen", rename it using `ormx::alter_sync`
+    #[ormx(
+        default = "NULL",
+        set,
+        get_range(NaiveDateTime),
+        renamed_from = "last_seen"
+    )]
     last_login: Option<NaiveDateTime>,
 }
 
@@ -99,3 +158,8 @@ enum Role {
     User,
     Admin,
 }
+
+// the type of `role` in `CREATE TABLE users`
+impl ormx::SqlType for Role {
+    const SQL_TYPE: &'static str = "user_role";
+}
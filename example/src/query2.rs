@@ -1,22 +1,32 @@
 use crate::User;
 use sqlx::PgPool;
 
+#[derive(ormx::OrderBy)]
+pub(crate) enum Order {
+    #[ormx(order_by = "first_name DESC")]
+    FirstName,
+    #[ormx(order_by = "id DESC")]
+    Newest,
+}
+
 pub(crate) async fn query_users(
     db: &PgPool,
     filter: Option<&str>,
+    order: Order,
     limit: Option<usize>,
 ) -> anyhow::Result<Vec<User>> {
     let result = ormx::conditional_query_as!(
         User,
-        r#"SELECT id AS user_id, first_name, last_name, email, disabled, role AS "role: _", last_login"#
-        "FROM users"
+        "SELECT" @columns(crate::User) "FROM users"
         Some(f) = filter => {
             "WHERE first_name LIKE" ?(f)
             "OR last_name LIKE" ?(f)
         }
-        "ORDER BY first_name DESC"
+        order_by!(order: crate::query2::Order)
         Some(l) = limit => {
             "LIMIT" ?(l as i64)
+        } else {
+            "LIMIT 100"
         }
     )
     .fetch_all(db)
@@ -24,3 +34,60 @@ pub(crate) async fn query_users(
 
     Ok(result)
 }
+
+pub(crate) async fn users_by_ids(db: &PgPool, ids: &[i32]) -> anyhow::Result<Vec<User>> {
+    let result = ormx::conditional_query_as!(
+        User,
+        "SELECT" @columns(crate::User) "FROM users"
+        "WHERE id" ?*(ids.iter().copied())
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(result)
+}
+
+pub(crate) async fn count_users(db: &PgPool, disabled: Option<bool>) -> anyhow::Result<i64> {
+    let count = ormx::conditional_query_scalar!(
+        "SELECT COUNT(*) AS \"count!\" FROM users"
+        Some(disabled) = disabled => {
+            match disabled {
+                true => { "WHERE disabled IS NOT NULL" },
+                false => { "WHERE disabled IS NULL" },
+            }
+        }
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(count)
+}
+
+pub(crate) async fn user_names(db: &PgPool, filter: Option<&str>) -> anyhow::Result<Vec<String>> {
+    let rows = ormx::conditional_query!(
+        "SELECT first_name, last_name FROM users"
+        Some(f) = filter => {
+            "WHERE first_name LIKE" ?(f)
+        }
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| format!("{} {}", row.first_name, row.last_name))
+        .collect())
+}
+
+pub(crate) async fn enable_users(db: &PgPool, ids: Option<&[i32]>) -> anyhow::Result<u64> {
+    let result = ormx::conditional_execute!(
+        "UPDATE users SET disabled = NULL"
+        Some(ids) = ids => {
+            "WHERE id" ?*(ids.iter().copied())
+        }
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
@@ -1,14 +1,23 @@
+use proc_macro2::Span;
 use syn::parse::{Parse, ParseStream};
 use syn::punctuated::Punctuated;
-use syn::{Attribute, Ident, Path, Result, Token, Type};
+use syn::{Attribute, Error, Expr, Ident, LitStr, Path, Result, Token, Type};
 
 pub enum TableAttr {
     // table = <string>
     Table(String),
+    // schema = <string>
+    Schema(String),
     // id = <ident>
     Id(Ident),
     // insertable [= [<attribute>]* <ident>]?
     Insertable(Option<Insertable>),
+    // ddl
+    Ddl(()),
+    // index(<option>, ..)
+    Index(Index),
+    // rename_all = <string>
+    RenameAll(RenameAll),
 }
 
 pub struct Insertable {
@@ -21,34 +30,125 @@ pub enum TableFieldAttr {
     Column(String),
     // custom_type
     CustomType(()),
-    // default
-    Default(()),
+    // json
+    Json(()),
+    // default [= <string>]?
+    Default(Option<String>),
     // get_one [= <ident>]? [(<type>)]?
     GetOne(Getter),
     // get_optional [= <ident>]? [(<type>)]?
     GetOptional(Getter),
-    // get_many [= <ident>]? [(<type>)]?
+    // get_many [= <ident>]? [(<type>? [, order_by = <string>]? [, limit]?)]?
     GetMany(Getter),
+    // get_range [= <ident>]? [(<type>? [, order_by = <string>]? [, limit]?)]?
+    GetRange(Getter),
     // set [= <ident>]?
     Set(Option<Ident>),
+    // read_only
+    ReadOnly(()),
+    // skip [(default = <expr>)]?
+    Skip(Skip),
+    // sql_type = <string>
+    SqlType(String),
+    // primary_key
+    PrimaryKey(()),
+    // auto_increment
+    AutoIncrement(()),
+    // identity = <ident>
+    Identity(Ident),
+    // unique
+    Unique(()),
+    // references = <string>
+    References(LitStr),
+    // on_delete = <ident>
+    OnDelete(Ident),
+    // on_update = <ident>
+    OnUpdate(Ident),
+    // renamed_from = <string>
+    RenamedFrom(String),
+    // flatten
+    Flatten(()),
+    // prefix = <string>
+    Prefix(String),
+}
+
+/// An index, declared using `#[ormx(index(..))]` or `#[ormx(unique)]`.
+#[derive(Clone)]
+pub struct Index {
+    pub span: Span,
+    // name = <string>
+    pub name: Option<String>,
+    // columns = (<ident>, ..)
+    pub columns: Vec<Ident>,
+    // unique
+    pub unique: bool,
+    // method = <ident>
+    pub method: Option<Ident>,
+    // concurrently
+    pub concurrently: bool,
+    // fulltext
+    pub fulltext: bool,
+    // spatial
+    pub spatial: bool,
+}
+
+/// A field which is not persisted, declared using `#[ormx(skip)]`.
+#[derive(Clone)]
+pub struct Skip {
+    // default = <expr>
+    pub default: Option<Expr>,
+}
+
+/// The naming convention of the columns, declared using `#[ormx(rename_all = "..")]`.
+#[derive(Copy, Clone)]
+pub enum RenameAll {
+    Camel,
+    Pascal,
+    ScreamingSnake,
 }
 
 #[derive(Clone)]
 pub struct Getter {
     pub func: Option<Ident>,
     pub arg_ty: Option<Type>,
+    pub order_by: Option<String>,
+    pub limit: bool,
 }
 
 pub enum PatchAttr {
     // table = <string>
     TableName(String),
+    // schema = <string>
+    Schema(String),
     Table(Path),
     Id(String),
+    // rename_all = <string>
+    RenameAll(RenameAll),
 }
 
 pub enum PatchFieldAttr {
     // column = <string>
     Column(String),
+    // json
+    Json(()),
+    // flatten
+    Flatten(()),
+    // prefix = <string>
+    Prefix(String),
+}
+
+pub enum EmbeddableFieldAttr {
+    // column = <string>
+    Column(String),
+    // custom_type
+    CustomType(()),
+    // sql_type = <string>
+    SqlType(String),
+}
+
+pub enum OrderByVariantAttr {
+    // order_by = <string>
+    OrderBy(String),
 }
 
 impl Parse for Getter {
@@ -59,14 +159,185 @@ impl Parse for Getter {
         } else {
             None
         };
-        let arg_ty = if input.peek(syn::token::Paren) {
+        let mut getter = Getter {
+            func,
+            arg_ty: None,
+            order_by: None,
+            limit: false,
+        };
+        if input.peek(syn::token::Paren) {
             let content;
             syn::parenthesized!(content in input);
-            Some(content.parse::<Type>()?)
-        } else {
-            None
+            while !content.is_empty() {
+                getter.parse_option(&content)?;
+                if !content.is_empty() {
+                    content.parse::<Token![,]>()?;
+                }
+            }
+        }
+        Ok(getter)
+    }
+}
+
+impl Getter {
+    // parses either `order_by = <string>`, `limit` or `<type>`
+    fn parse_option(&mut self, input: ParseStream) -> Result<()> {
+        let is_last = |i: ParseStream| i.is_empty() || i.peek(Token![,]);
+        let fork = input.fork();
+        if let Ok(ident) = fork.parse::<Ident>() {
+            if ident == "order_by" && fork.peek(Token![=]) {
+                input.parse::<Ident>()?;
+                input.parse::<Token![=]>()?;
+                let order_by = input.parse::<syn::LitStr>()?.value();
+                return crate::utils::set_once(&mut self.order_by, order_by);
+            }
+            if ident == "limit" && is_last(&fork) {
+                input.parse::<Ident>()?;
+                if self.limit {
+                    return Err(input.error("duplicate attribute"));
+                }
+                self.limit = true;
+                return Ok(());
+            }
+        }
+        crate::utils::set_once(&mut self.arg_ty, input.parse::<Type>()?)
+    }
+}
+
+impl Parse for Skip {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut skip = Skip { default: None };
+        if input.peek(syn::token::Paren) {
+            let content;
+            syn::parenthesized!(content in input);
+            let ident = content.parse::<Ident>()?;
+            if ident != "default" {
+                return Err(Error::new(
+                    ident.span(),
+                    "expected `default = <expression>`",
+                ));
+            }
+            content.parse::<Token![=]>()?;
+            skip.default = Some(content.parse::<Expr>()?);
+            if !content.is_empty() {
+                return Err(content.error("unexpected token"));
+            }
+        }
+        Ok(skip)
+    }
+}
+
+impl Parse for Index {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut index = Index {
+            span: input.span(),
+            name: None,
+            columns: vec![],
+            unique: false,
+            method: None,
+            concurrently: false,
+            fulltext: false,
+            spatial: false,
+        };
+        let content;
+        syn::parenthesized!(content in input);
+        while !content.is_empty() {
+            index.parse_option(&content)?;
+            if !content.is_empty() {
+                content.parse::<Token![,]>()?;
+            }
+        }
+        if index.columns.is_empty() {
+            return Err(Error::new(index.span, "expected `columns = (<field>, ..)`"));
+        }
+        Ok(index)
+    }
+}
+
+impl Index {
+    fn parse_option(&mut self, input: ParseStream) -> Result<()> {
+        let ident = input.parse::<Ident>()?;
+        let flag = |flag: &mut bool| match std::mem::replace(flag, true) {
+            false => Ok(()),
+            true => Err(Error::new(ident.span(), "duplicate attribute")),
         };
-        Ok(Getter { func, arg_ty })
+        match &*ident.to_string() {
+            "name" => {
+                input.parse::<Token![=]>()?;
+                let name = input.parse::<syn::LitStr>()?.value();
+                crate::utils::set_once(&mut self.name, name)
+            }
+            "columns" => {
+                input.parse::<Token![=]>()?;
+                let content;
+                syn::parenthesized!(content in input);
+                let columns = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?;
+                if !self.columns.is_empty() {
+                    return Err(Error::new(ident.span(), "duplicate attribute"));
+                }
+                self.columns = columns.into_iter().collect();
+                Ok(())
+            }
+            "method" => {
+                input.parse::<Token![=]>()?;
+                crate::utils::set_once(&mut self.method, input.parse::<Ident>()?)
+            }
+            "unique" => flag(&mut self.unique),
+            "concurrently" => flag(&mut self.concurrently),
+            "fulltext" => flag(&mut self.fulltext),
+            "spatial" => flag(&mut self.spatial),
+            _ => Err(Error::new(ident.span(), "unknown attribute")),
+        }
+    }
+}
+
+impl RenameAll {
+    const ALL: &'static [(&'static str, RenameAll)] = &[
+        ("camelCase", RenameAll::Camel),
+        ("PascalCase", RenameAll::Pascal),
+        ("SCREAMING_SNAKE_CASE", RenameAll::ScreamingSnake),
+    ];
+
+    /// Converts the name of a field, e.g. `first_name`, to the name of its column.
+    pub fn apply(self, name: &str) -> String {
+        match self {
+            RenameAll::Camel | RenameAll::Pascal => {
+                let mut capitalize = matches!(self, RenameAll::Pascal);
+                let mut renamed = String::with_capacity(name.len());
+                for c in name.chars() {
+                    if c == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        renamed.extend(c.to_uppercase());
+                        capitalize = false;
+                    } else {
+                        renamed.push(c);
+                    }
+                }
+                renamed
+            }
+            RenameAll::ScreamingSnake => name.to_uppercase(),
+        }
+    }
+}
+
+impl Parse for RenameAll {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let lit = input.parse::<syn::LitStr>()?;
+        RenameAll::ALL
+            .iter()
+            .find(|(name, _)| lit.value() == *name)
+            .map(|(_, rename_all)| *rename_all)
+            .ok_or_else(|| {
+                let expected = RenameAll::ALL
+                    .iter()
+                    .map(|(name, _)| format!("\"{}\"", name));
+                let message = format!(
+                    "expected one of {}",
+                    expected.collect::<Vec<_>>().join(", ")
+                );
+                Error::new(lit.span(), message)
+            })
     }
 }
 
@@ -132,8 +403,12 @@ macro_rules! impl_parse {
 
 impl_parse!(TableAttr {
     "table" => Table(= String),
+    "schema" => Schema(= String),
     "id" => Id(= Ident),
-    "insertable" => Insertable((= Insertable)?)
+    "insertable" => Insertable((= Insertable)?),
+    "ddl" => Ddl(),
+    "index" => Index(Index),
+    "rename_all" => RenameAll(= RenameAll)
 });
 
 impl_parse!(TableFieldAttr {
@@ -141,17 +416,85 @@ impl_parse!(TableFieldAttr {
     "get_one" => GetOne(Getter),
     "get_optional" => GetOptional(Getter),
     "get_many" => GetMany(Getter),
+    "get_range" => GetRange(Getter),
     "set" => Set((= Ident)?),
     "custom_type" => CustomType(),
-    "default" => Default()
+    "json" => Json(),
+    "read_only" => ReadOnly(),
+    "skip" => Skip(Skip),
+    "default" => Default((= String)?),
+    "sql_type" => SqlType(= String),
+    "primary_key" => PrimaryKey(),
+    "auto_increment" => AutoIncrement(),
+    "identity" => Identity(= Ident),
+    "unique" => Unique(),
+    "references" => References(= LitStr),
+    "on_delete" => OnDelete(= Ident),
+    "on_update" => OnUpdate(= Ident),
+    "renamed_from" => RenamedFrom(= String),
+    "flatten" => Flatten(),
+    "prefix" => Prefix(= String)
 });
 
 impl_parse!(PatchAttr {
     "table" => Table(= Path),
     "table_name" => TableName(= String),
-    "id" => Id(= String)
+    "schema" => Schema(= String),
+    "id" => Id(= String),
+    "rename_all" => RenameAll(= RenameAll)
 });
 
 impl_parse!(PatchFieldAttr {
-    "column" => Column(= String)
+    "column" => Column(= String),
+    "json" => Json(),
+    "flatten" => Flatten(),
+    "prefix" => Prefix(= String)
+});
+
+impl_parse!(EmbeddableFieldAttr {
+    "column" => Column(= String),
+    "custom_type" => CustomType(),
+    "sql_type" => SqlType(= String)
+});
+
+impl_parse!(OrderByVariantAttr {
+    "order_by" => OrderBy(= String)
 });
+
+#[cfg(test)]
+mod tests {
+    use super::RenameAll;
+
+    #[test]
+    fn rename_all() {
+        for (name, camel, pascal, screaming) in [
+            ("id", "id", "Id", "ID"),
+            ("first_name", "firstName", "FirstName", "FIRST_NAME"),
+            (
+                "address_line_2",
+                "addressLine2",
+                "AddressLine2",
+                "ADDRESS_LINE_2",
+            ),
+        ] {
+            assert_eq!(RenameAll::Camel.apply(name), camel);
+            assert_eq!(RenameAll::Pascal.apply(name), pascal);
+            assert_eq!(RenameAll::ScreamingSnake.apply(name), screaming);
+        }
+    }
+
+    #[test]
+    fn parse_rename_all() {
+        let rename_all: RenameAll = syn::parse_str("\"PascalCase\"").unwrap();
+        assert!(matches!(rename_all, RenameAll::Pascal));
+
+        let err = match syn::parse_str::<RenameAll>("\"snake_case\"") {
+            Ok(_) => panic!("expected an error"),
+            Err(err) => err,
+        };
+        assert_eq!(
+            err.to_string(),
+            "expected one of \"camelCase\", \"PascalCase\", \"SCREAMING_SNAKE_CASE\""
+        );
+    }
+}
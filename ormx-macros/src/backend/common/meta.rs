@@ -0,0 +1,128 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::Ident;
+
+use crate::backend::Backend;
+use crate::table::{ReferentialAction, Table};
+
+pub fn impl_table_meta<B: Backend>(table: &Table<B>) -> TokenStream {
+    let table_ident = &table.ident;
+    let vis = &table.vis;
+    let table_name = B::stored_name(&B::quote_ident(&table.table));
+    let schema = table.schema.as_ref();
+    let schema = option(&schema.map(|schema| B::stored_name(&B::quote_ident(schema))));
+    let qualified_table = table.qualified_table();
+    let id_column = table.id.stored_name();
+    let column_list = table.select_column_list();
+    let columns_macro =
+        crate::query2::splice::columns_macro(table_ident, &column_list, &table.query_as_ident());
+
+    let primary_key = table.primary_key();
+    let columns = table.fields.iter().map(|field| {
+        let name = field.stored_name();
+        let field_name = field.field_name();
+        let nullable = field.is_nullable();
+        let default = field.default;
+        let read_only = field.read_only;
+        let custom_type = field.custom_type;
+        let sql_type = match table.ddl {
+            true => {
+                let sql_type = field.sql_type();
+                quote!(Some(#sql_type))
+            }
+            false => quote!(None),
+        };
+        let default_expr = option(&field.default_expr);
+        let renamed_from = option(&field.renamed_from);
+        let comment = option(&field.comment);
+        let primary_key = primary_key.iter().any(|pk| pk.field == field.field);
+        let auto_increment = field.auto_increment || field.identity.is_some();
+        let references = match &field.references {
+            Some(references) => {
+                let (table, column) = (&references.table, &references.column);
+                let no_action = ReferentialAction::NoAction;
+                let on_delete = references.on_delete.unwrap_or(no_action).variant();
+                let on_update = references.on_update.unwrap_or(no_action).variant();
+                quote! {
+                    Some(ormx::ForeignKeyMeta {
+                        table: #table,
+                        column: #column,
+                        on_delete: #on_delete,
+                        on_update: #on_update,
+                    })
+                }
+            }
+            None => quote!(None),
+        };
+        quote! {
+            ormx::ColumnMeta {
+                name: #name,
+                field: #field_name,
+                nullable: #nullable,
+                default: #default,
+                read_only: #read_only,
+                custom_type: #custom_type,
+                sql_type: #sql_type,
+                default_expr: #default_expr,
+                primary_key: #primary_key,
+                auto_increment: #auto_increment,
+                references: #references,
+                renamed_from: #renamed_from,
+                comment: #comment,
+            }
+        }
+    });
+
+    let indexes = table.indexes.iter().map(|index| {
+        let name = table.index_name(index);
+        let columns = index
+            .columns
+            .iter()
+            .map(|column| table.field(column).stored_name());
+        let unique = index.unique;
+        quote! {
+            ormx::IndexMeta {
+                name: #name,
+                columns: &[ #( #columns ),* ],
+                unique: #unique,
+            }
+        }
+    });
+
+    let markers = table.fields.iter().enumerate().map(|(idx, field)| {
+        let ty = &field.ty;
+        let marker = field.field_name().replace('.', "_").to_uppercase();
+        let marker = Ident::new(&marker, Span::call_site());
+        let doc = format!("Column `{}`", field.column_name);
+        quote! {
+            #[doc = #doc]
+            #vis const #marker: ormx::Column<Self, #ty> =
+                ormx::Column::new(&<Self as ormx::TableMeta>::COLUMNS[#idx]);
+        }
+    });
+
+    quote! {
+        impl ormx::TableMeta for #table_ident {
+            const TABLE: &'static str = #table_name;
+            const SCHEMA: Option<&'static str> = #schema;
+            const QUALIFIED_TABLE: &'static str = #qualified_table;
+            const ID_COLUMN: &'static str = #id_column;
+            const COLUMNS: &'static [ormx::ColumnMeta] = &[ #( #columns ),* ];
+            const SELECT_COLUMNS: &'static str = #column_list;
+            const INDEXES: &'static [ormx::IndexMeta] = &[ #( #indexes ),* ];
+        }
+
+        #columns_macro
+
+        impl #table_ident {
+            #( #markers )*
+        }
+    }
+}
+
+fn option(value: &Option<String>) -> TokenStream {
+    match value {
+        Some(value) => quote!(Some(#value)),
+        None => quote!(None),
+    }
+}
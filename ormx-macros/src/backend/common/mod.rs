@@ -2,44 +2,69 @@
 
 use proc_macro2::TokenStream;
 use quote::quote;
-use syn::{Ident, Type, Visibility};
+use syn::ext::IdentExt;
+use syn::{Ident, Type};
 
+pub use meta::*;
+pub use query2::*;
+pub use schema::*;
 pub use table::*;
 
-use crate::attrs::Insertable;
+use crate::attrs::{Getter, Insertable};
 use crate::backend::Backend;
 use crate::patch::Patch;
 use crate::table::Table;
+use crate::utils::{query, record_field};
 
+mod meta;
+mod query2;
+mod schema;
 mod table;
 
 pub(crate) fn getters<B: Backend>(table: &Table<B>) -> TokenStream {
     let column_list = table.select_column_list();
-    let vis = &table.vis;
     let mut getters = TokenStream::new();
 
     for field in table.fields.iter() {
-        let sql = format!(
-            "SELECT {} FROM {} WHERE {} = {}",
-            column_list,
-            table.table,
-            field.column(),
-            B::Bindings::default().next().unwrap()
-        );
+        // `col = NULL` is never true, so nullable columns need to be compared differently
+        let equals = |bindings: &mut B::Bindings| {
+            let placeholder = bindings.next().unwrap();
+            match field.is_nullable() {
+                true => B::null_safe_eq(&field.column(), &placeholder),
+                false => format!("{} = {}", field.column(), placeholder),
+            }
+        };
 
         if let Some(getter) = &field.get_one {
-            let (func, arg) = getter.or_fallback(&field);
-            getters.extend(get_one(vis, &func, &arg, &sql));
+            let (func, arg) = getter.or_fallback(field);
+            let sql = getter_sql(table, &column_list, getter, equals);
+            getters.extend(get_one(table, &func, &arg, &sql));
         }
 
         if let Some(getter) = &field.get_optional {
-            let (func, arg) = getter.or_fallback(&field);
-            getters.extend(get_optional(vis, &func, &arg, &sql));
+            let (func, arg) = getter.or_fallback(field);
+            let sql = getter_sql(table, &column_list, getter, equals);
+            getters.extend(get_optional(table, &func, &arg, &sql));
         }
 
         if let Some(getter) = &field.get_many {
-            let (func, arg) = getter.or_fallback(&field);
-            getters.extend(get_many(vis, &func, &arg, &sql));
+            let (func, arg) = getter.or_fallback(field);
+            let sql = getter_sql(table, &column_list, getter, equals);
+            getters.extend(get_many(table, &func, &arg, &sql, getter.limit));
+        }
+
+        if let Some(getter) = &field.get_range {
+            let default_name = format!("by_{}_between", field.field.unraw());
+            let (func, arg) = getter.or_fallback_named(field, &default_name);
+            let sql = getter_sql(table, &column_list, getter, |bindings| {
+                format!(
+                    "{} BETWEEN {} AND {}",
+                    field.column(),
+                    bindings.next().unwrap(),
+                    bindings.next().unwrap()
+                )
+            });
+            getters.extend(get_range(table, &func, &arg, &sql, getter.limit));
         }
     }
 
@@ -51,48 +76,132 @@ pub(crate) fn getters<B: Backend>(table: &Table<B>) -> TokenStream {
     }
 }
 
-pub fn get_one(vis: &Visibility, ident: &Ident, by_ty: &Type, sql: &str) -> TokenStream {
+/// Builds the query of a getter, appending `ORDER BY` and `LIMIT` if requested.
+fn getter_sql<B: Backend>(
+    table: &Table<B>,
+    column_list: &str,
+    getter: &Getter,
+    condition: impl FnOnce(&mut B::Bindings) -> String,
+) -> String {
+    let mut bindings = B::Bindings::default();
+    let mut sql = format!(
+        "SELECT {} FROM {} WHERE {}",
+        column_list,
+        table.qualified_table(),
+        condition(&mut bindings)
+    );
+    if let Some(order_by) = &getter.order_by {
+        sql.push_str(&format!(" ORDER BY {}", order_by));
+    }
+    if getter.limit {
+        sql.push_str(&format!(" LIMIT {}", bindings.next().unwrap()));
+    }
+    sql
+}
+
+/// Returns the additional parameter and argument of a getter with `limit`.
+fn limit_argument(limit: bool) -> (TokenStream, Vec<TokenStream>) {
+    match limit {
+        true => (quote!(limit: i64,), vec![quote!(limit)]),
+        false => (quote!(), vec![]),
+    }
+}
+
+pub fn get_one<B: Backend>(
+    table: &Table<B>,
+    ident: &Ident,
+    by_ty: &Type,
+    sql: &str,
+) -> TokenStream {
+    let vis = &table.vis;
+    let query = table.query_as_self(sql, &[quote!(by)]);
     quote! {
         #vis async fn #ident(
             db: impl sqlx::Executor<'_, Database = ormx::Db>,
             by: #by_ty,
         ) -> sqlx::Result<Self> {
-            sqlx::query_as!(Self, #sql, by)
+            #query
                 .fetch_one(db)
                 .await
         }
     }
 }
 
-pub fn get_optional(vis: &Visibility, ident: &Ident, by_ty: &Type, sql: &str) -> TokenStream {
+pub fn get_optional<B: Backend>(
+    table: &Table<B>,
+    ident: &Ident,
+    by_ty: &Type,
+    sql: &str,
+) -> TokenStream {
+    let vis = &table.vis;
+    let query = table.query_as_self(sql, &[quote!(by)]);
     quote! {
         #vis async fn #ident(
             db: impl sqlx::Executor<'_, Database = ormx::Db>,
             by: #by_ty,
         ) -> sqlx::Result<Option<Self>> {
-            sqlx::query_as!(Self, #sql, by)
+            #query
                 .fetch_optional(db)
                 .await
         }
     }
 }
 
-pub fn get_many(vis: &Visibility, ident: &Ident, by_ty: &Type, sql: &str) -> TokenStream {
+pub fn get_many<B: Backend>(
+    table: &Table<B>,
+    ident: &Ident,
+    by_ty: &Type,
+    sql: &str,
+    limit: bool,
+) -> TokenStream {
+    let vis = &table.vis;
+    let (limit_param, limit_arg) = limit_argument(limit);
+    let query = table.query_as_self(sql, &[&[quote!(by)], &limit_arg[..]].concat());
     quote! {
         #vis async fn #ident(
             db: impl sqlx::Executor<'_, Database = ormx::Db>,
             by: #by_ty,
+            #limit_param
+        ) -> sqlx::Result<Vec<Self>> {
+            #query
+                .fetch_all(db)
+                .await
+        }
+    }
+}
+
+pub fn get_range<B: Backend>(
+    table: &Table<B>,
+    ident: &Ident,
+    by_ty: &Type,
+    sql: &str,
+    limit: bool,
+) -> TokenStream {
+    let vis = &table.vis;
+    let (limit_param, limit_arg) = limit_argument(limit);
+    let query = table.query_as_self(sql, &[&[quote!(from), quote!(to)], &limit_arg[..]].concat());
+    quote! {
+        #vis async fn #ident(
+            db: impl sqlx::Executor<'_, Database = ormx::Db>,
+            from: #by_ty,
+            to: #by_ty,
+            #limit_param
         ) -> sqlx::Result<Vec<Self>> {
-            sqlx::query_as!(Self, #sql, by)
+            #query
                 .fetch_all(db)
                 .await
         }
     }
 }
 
+/// Generates the setters. Since the read-only fields may be changed by the database when the row
+/// is updated, they are refreshed afterwards, see [write_and_refresh]. If the backend doesn't
+/// support `RETURNING`, this requires a second query, so the setters take a connection which
+/// can be acquired, like `Table::update`.
 pub fn setters<B: Backend>(table: &Table<B>) -> TokenStream {
     let vis = &table.vis;
     let mut setters = TokenStream::new();
+    let select = !B::RETURNING && table.read_only_fields().next().is_some();
 
     for field in table.fields.iter() {
         let field_ident = &field.field;
@@ -102,21 +211,37 @@ pub fn setters<B: Backend>(table: &Table<B>) -> TokenStream {
             let mut bindings = B::Bindings::default();
             let sql = format!(
                 "UPDATE {} SET {} = {} WHERE {} = {}",
-                table.table,
+                table.qualified_table(),
                 field.column(),
                 bindings.next().unwrap(),
                 table.id.column(),
                 bindings.next().unwrap(),
             );
+            let args = [
+                field.bind(quote!(value)),
+                quote!(<Self as ormx::Table>::id(self)),
+            ];
+            let (db, write) = match select {
+                true => {
+                    let write = write_and_refresh(table, &sql, &args, quote!(&mut *_conn));
+                    let write = quote! {
+                        let mut _conn = sqlx::Acquire::acquire(db).await?;
+                        #write
+                    };
+                    (quote!(sqlx::Acquire<'_, Database = ormx::Db>), write)
+                }
+                false => {
+                    let write = write_and_refresh(table, &sql, &args, quote!(db));
+                    (quote!(sqlx::Executor<'_, Database = ormx::Db>), write)
+                }
+            };
             setters.extend(quote! {
                 #vis async fn #fn_name(
                     &mut self,
-                    db: impl sqlx::Executor<'_, Database = ormx::Db>,
+                    db: impl #db,
                     value: #field_ty
                 ) -> sqlx::Result<()> {
-                    sqlx::query!(#sql, value, <Self as ormx::Table>::id(self))
-                        .execute(db)
-                        .await?;
+                    #write
                     self.#field_ident = value;
                     Ok(())
                 }
@@ -132,31 +257,90 @@ pub fn setters<B: Backend>(table: &Table<B>) -> TokenStream {
     }
 }
 
+/// Generates the statements executing `sql`, which writes the row `self`, and refreshing the
+/// read-only fields afterwards. If the backend supports it, they are returned by `sql` using
+/// `RETURNING`. Otherwise, they are selected by a second query, so `db` is used twice.
+pub(crate) fn write_and_refresh<B: Backend>(
+    table: &Table<B>,
+    sql: &str,
+    args: &[TokenStream],
+    db: TokenStream,
+) -> TokenStream {
+    let refreshed = table.read_only_fields().collect::<Vec<_>>();
+    if refreshed.is_empty() {
+        let write = query(sql, args);
+        return quote!(#write.execute(#db).await?;);
+    }
+    let columns = refreshed
+        .iter()
+        .map(|field| field.fmt_for_select())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let fetch = match B::RETURNING {
+        true => {
+            let write = query(&format!("{} RETURNING {}", sql, columns), args);
+            quote!(let _row = #write.fetch_one(#db).await?;)
+        }
+        false => {
+            let write = query(sql, args);
+            let select_sql = format!(
+                "SELECT {} FROM {} WHERE {} = {}",
+                columns,
+                table.qualified_table(),
+                table.id.column(),
+                B::Bindings::default().next().unwrap()
+            );
+            let select = query(&select_sql, &[quote!(<Self as ormx::Table>::id(self))]);
+            quote! {
+                #write.execute(#db).await?;
+                let _row = #select.fetch_one(#db).await?;
+            }
+        }
+    };
+    let assignments = refreshed.iter().map(|field| {
+        let (ident, ty) = (&field.field, &field.ty);
+        let value = record_field(quote!(_row), ident, &quote!(#ty));
+        quote!(self.#ident = #value;)
+    });
+    quote! {
+        #fetch
+        #( #assignments )*
+    }
+}
+
 pub(crate) fn impl_patch<B: Backend>(patch: &Patch) -> TokenStream {
     let patch_ident = &patch.ident;
     let table_path = &patch.table;
-    let field_idents = &patch
-        .fields
-        .iter()
-        .map(|field| &field.ident)
-        .collect::<Vec<&Ident>>();
+    let field_idents = patch.struct_fields();
 
     let mut bindings = B::Bindings::default();
     let mut assignments = Vec::with_capacity(patch.fields.len());
     for field in &patch.fields {
-        let fragment = format!("{} = {}", field.column, bindings.next().unwrap());
+        let column = match field.renamed {
+            true => B::quote_renamed(&field.column),
+            false => B::quote_ident(&field.column),
+        };
+        let fragment = format!("{} = {}", column, bindings.next().unwrap());
         assignments.push(fragment);
     }
     let assignments = assignments.join(", ");
 
     let sql = format!(
         "UPDATE {} SET {} WHERE {} = {}",
-        &patch.table_name,
+        B::qualified_name(patch.schema.as_deref(), &patch.table_name),
         assignments,
-        patch.id,
+        B::quote_ident(&patch.id),
         bindings.next().unwrap()
     );
 
+    let mut args = patch
+        .fields
+        .iter()
+        .map(|field| field.arg(quote!(self)))
+        .collect::<Vec<_>>();
+    args.push(quote!(id));
+    let query = query(&sql, &args);
+
     let box_future = crate::utils::box_future();
     quote! {
         impl ormx::Patch for #patch_ident {
@@ -172,7 +356,7 @@ pub(crate) fn impl_patch<B: Backend>(patch: &Patch) -> TokenStream {
                 id: <Self::Table as ormx::Table>::Id,
             ) -> #box_future<'a, sqlx::Result<()>> {
                 Box::pin(async move {
-                    sqlx::query!(#sql, #( self.#field_idents, )* id)
+                    #query
                         .execute(db)
                         .await?;
                     Ok(())
@@ -188,11 +372,9 @@ pub(crate) fn insert_struct<B: Backend>(table: &Table<B>) -> TokenStream {
         None => return quote!(),
     };
     let vis = &table.vis;
-    let insert_fields = table.insertable_fields().map(|field| {
-        let ident = &field.field;
-        let ty = &field.ty;
-        quote!(#vis #ident: #ty)
-    });
+    let insert_fields = Table::struct_fields(table.insertable_fields())
+        .into_iter()
+        .map(|(ident, ty)| quote!(#vis #ident: #ty));
 
     quote! {
         #(#attrs)*
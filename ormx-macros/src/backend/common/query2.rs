@@ -0,0 +1,691 @@
+use std::collections::BTreeMap;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{Error, Expr, Ident, Path};
+
+use crate::backend::Backend;
+use crate::query2::{Columns, ConditionalQuery, Fragment, Output};
+use crate::utils::RUNTIME_QUERIES;
+
+/// Expands a conditional query.
+///
+/// At runtime, the SQL is assembled by an `ormx::exports::QueryBuilder` while walking through the
+/// conditions. At compile time, the query is checked by one `sqlx::query*!` per combination of
+/// alternatives returned by [checks]. Since the queries are only checked and never executed, the
+/// arguments are replaced by dummies of the same type.
+///
+/// The rows are mapped by the query in which every condition takes its default alternative, which
+/// is built with `NULL` arguments, and executed with the SQL and the arguments assembled at runtime.
+/// Like `sqlx::query_as!`, it reads the columns by their position, so the conditions must not change
+/// the returned columns, see [check_columns]. Tables whose columns are spliced using `@columns(..)`
+/// are read by the names of their columns instead, using the hidden function generated by
+/// `derive(Table)`, so their columns may differ between branches. They are checked using the struct
+/// their rows are read into, like the queries generated by `derive(Table)`. Scalars are read from
+/// the only column of a row, and every checked query has to return the same type.
+///
+/// With `runtime-queries`, there are no checks, and the rows are mapped using `FromRow`, unless the
+/// columns of a table are spliced.
+pub fn conditional_query<B: Backend>(query: &ConditionalQuery) -> TokenStream {
+    let mut ids = Ids::default();
+    let nodes = lower(&query.fragments, &mut ids);
+
+    let witnesses = (0..ids.args).map(|idx| format_ident!("__ormx_arg_{}", idx));
+    let build = build(&nodes);
+    let (sql, args) = render::<B>(&nodes, &Choices::new());
+    let nulls = args.iter().map(RenderedArg::null::<B>).collect::<Vec<_>>();
+    let table = match &query.output {
+        Output::Struct(out) => table(&query.fragments, out),
+        _ => None,
+    };
+    let mapping = match (&query.output, table) {
+        (Output::Struct(_), Some(Columns { path: table, .. })) => {
+            quote! {
+                sqlx::query::<ormx::Db>(#sql).try_map(
+                    |row: <ormx::Db as sqlx::Database>::Row| #table::__ormx_from_row(&row)
+                )
+            }
+        }
+        (Output::Struct(out), _) if RUNTIME_QUERIES => {
+            quote!(sqlx::query_as::<ormx::Db, #out>(#sql))
+        }
+        (Output::Struct(out), _) => quote!(sqlx::query_as!(#out, #sql, #( #nulls ),*)),
+        (Output::Scalar, _) if RUNTIME_QUERIES => quote!(sqlx::query_scalar::<ormx::Db, _>(#sql)),
+        (Output::Scalar, _) => quote!(sqlx::query_scalar!(#sql, #( #nulls ),*)),
+        (Output::Nothing, _) => quote!(sqlx::query::<ormx::Db>(#sql)),
+        (Output::Record, _) if RUNTIME_QUERIES => {
+            let message = "the type of an anonymous record can't be inferred without a \
+                           database, so `conditional_query!` is not available with the \
+                           `runtime-queries` feature";
+            return Error::new(Span::call_site(), message).to_compile_error();
+        }
+        (Output::Record, _) => quote!(sqlx::query!(#sql, #( #nulls ),*)),
+    };
+    if RUNTIME_QUERIES {
+        return quote! {{
+            #( let #witnesses = std::marker::PhantomData; )*
+            let mut __ormx_query = ormx::exports::QueryBuilder::new();
+            #build
+            __ormx_query.build(#mapping)
+        }};
+    }
+
+    let by_position = match (&query.output, table) {
+        (Output::Struct(_), None) => Some(STRUCT_BY_POSITION),
+        (Output::Record, _) => Some(RECORD_BY_POSITION),
+        _ => None,
+    };
+    if let Some(message) = by_position {
+        if let Err(err) = check_columns(&nodes, &mut ColumnScanner::default(), None, message) {
+            return err.to_compile_error();
+        }
+    }
+
+    let checks = checks(&nodes).into_iter().map(|choices| {
+        let (sql, args) = render::<B>(&nodes, &choices);
+        let args = args.iter().map(RenderedArg::dummy::<B>);
+        match (&query.output, table) {
+            // tables with skipped, flattened or JSON fields are read into a hidden struct
+            (Output::Struct(_), Some(table)) => {
+                let query_as = query_as_path(table);
+                quote!(let _ = sqlx::query_as!(#query_as, #sql, #( #args ),*);)
+            }
+            (Output::Struct(out), None) => {
+                quote!(let _ = sqlx::query_as!(#out, #sql, #( #args ),*);)
+            }
+            (Output::Scalar, _) => quote! {
+                ormx::exports::same_output(
+                    &__ormx_mapping,
+                    sqlx::query_scalar!(#sql, #( #args ),*),
+                );
+            },
+            (Output::Record | Output::Nothing, _) => {
+                quote!(let _ = sqlx::query!(#sql, #( #args ),*);)
+            }
+        }
+    });
+
+    quote! {{
+        #( let #witnesses = std::marker::PhantomData; )*
+        let mut __ormx_query = ormx::exports::QueryBuilder::new();
+        #build
+        let __ormx_mapping = #mapping;
+        if false {
+            #( #checks )*
+        }
+        __ormx_query.build(__ormx_mapping)
+    }}
+}
+
+const STRUCT_BY_POSITION: &str =
+    "the fields returned by `conditional_query_as!` are read by the position of their columns, so \
+     conditions can't change the returned columns, unless the columns of the returned table are \
+     spliced using `@columns(..)`";
+
+const RECORD_BY_POSITION: &str =
+    "the fields of the record returned by `conditional_query!` are read by their position, so \
+     conditions can't change the returned columns";
+
+/// Returns the table the rows are mapped to, if its columns are spliced into the query using
+/// `@columns(..)`.
+fn table<'a>(fragments: &'a [Fragment], out: &Path) -> Option<&'a Columns> {
+    let ident = |path: &Path| path.segments.last().map(|segment| segment.ident.clone());
+    fragments.iter().find_map(|fragment| match fragment {
+        Fragment::Columns(columns) if ident(&columns.path) == ident(out) => Some(columns),
+        Fragment::Condition(condition) => {
+            table(&condition.then, out).or_else(|| table(&condition.otherwise, out))
+        }
+        Fragment::Match(m) => m.arms.iter().find_map(|(_, arm)| table(arm, out)),
+        _ => None,
+    })
+}
+
+/// Returns the path of the struct `sqlx::query_as!` reads the rows of a table into, which is
+/// declared next to the table.
+fn query_as_path(table: &Columns) -> Path {
+    let mut path = table.path.clone();
+    if let Some(last) = path.segments.last_mut() {
+        last.ident = table.query_as.clone();
+    }
+    path
+}
+
+/// A fragment of a conditional query, with every argument and condition numbered.
+enum Node<'a> {
+    Sql(String),
+    Arg(Ident, &'a Expr),
+    List(Ident, &'a Expr),
+    Choice(Choice<'a>),
+}
+
+/// A condition, with one list of nodes for every alternative.
+/// An `if let` has two alternatives (taken, not taken), a `match` one for each of its arms.
+struct Choice<'a> {
+    id: usize,
+    fragment: &'a Fragment,
+    alternatives: Vec<Vec<Node<'a>>>,
+    default: usize,
+}
+
+#[derive(Default)]
+struct Ids {
+    args: usize,
+    choices: usize,
+}
+
+impl Ids {
+    /// Returns the name of the variable recording the type of the next argument.
+    fn next_arg(&mut self) -> Ident {
+        let witness = format_ident!("__ormx_arg_{}", self.args);
+        self.args += 1;
+        witness
+    }
+}
+
+fn lower<'a>(fragments: &'a [Fragment], ids: &mut Ids) -> Vec<Node<'a>> {
+    fragments
+        .iter()
+        .map(|fragment| match fragment {
+            Fragment::Sql(sql) => Node::Sql(sql.value()),
+            Fragment::Columns(columns) => Node::Sql(columns.sql.value()),
+            Fragment::Arg(arg) => Node::Arg(ids.next_arg(), arg),
+            Fragment::List(list) => Node::List(ids.next_arg(), list),
+            Fragment::Condition(condition) => {
+                let id = ids.choices;
+                ids.choices += 1;
+                Node::Choice(Choice {
+                    id,
+                    fragment,
+                    alternatives: vec![
+                        lower(&condition.then, ids),
+                        lower(&condition.otherwise, ids),
+                    ],
+                    default: 1,
+                })
+            }
+            Fragment::Match(m) => {
+                let id = ids.choices;
+                ids.choices += 1;
+                Node::Choice(Choice {
+                    id,
+                    fragment,
+                    alternatives: m.arms.iter().map(|(_, arm)| lower(arm, ids)).collect(),
+                    default: 0,
+                })
+            }
+        })
+        .collect()
+}
+
+/// Generates the code assembling the query at runtime.
+fn build(nodes: &[Node]) -> TokenStream {
+    nodes
+        .iter()
+        .map(|node| match node {
+            Node::Sql(sql) => quote!(__ormx_query.push_sql(#sql);),
+            Node::Arg(witness, arg) => {
+                quote!(__ormx_query.push_arg(ormx::exports::bind(&#witness, #arg));)
+            }
+            Node::List(witness, list) => {
+                quote!(__ormx_query.push_list(ormx::exports::bind_list(&#witness, #list));)
+            }
+            Node::Choice(choice) => match choice.fragment {
+                Fragment::Condition(condition) => {
+                    let (pat, expr) = (&condition.pat, &condition.expr);
+                    let then = build(&choice.alternatives[0]);
+                    match build(&choice.alternatives[1]) {
+                        otherwise if otherwise.is_empty() => quote! {
+                            if let #pat = #expr { #then }
+                        },
+                        otherwise => quote! {
+                            if let #pat = #expr { #then } else { #otherwise }
+                        },
+                    }
+                }
+                Fragment::Match(m) => {
+                    let expr = &m.expr;
+                    let pats = m.arms.iter().map(|(pat, _)| pat);
+                    let arms = choice.alternatives.iter().map(|arm| build(arm));
+                    quote! {
+                        match #expr { #( #pats => { #arms } )* }
+                    }
+                }
+                _ => unreachable!(),
+            },
+        })
+        .collect()
+}
+
+/// The alternatives taken by the conditions of a query, by their id. Conditions which are missing
+/// take their default alternative.
+type Choices = BTreeMap<usize, usize>;
+
+/// Returns the choices of all queries which have to be checked: one in which every condition takes
+/// its default alternative, one for every other alternative, and one for every pair of other
+/// alternatives of different conditions, since their fragments may depend on each other (e.g. two
+/// `JOIN`s, or a `WHERE` followed by an `AND`). The number of checked queries therefore grows
+/// quadratically with the number of conditions, instead of exponentially.
+fn checks(nodes: &[Node]) -> Vec<Choices> {
+    /// Collects every alternative which is not taken by default, including the alternatives taken
+    /// by the conditions containing it.
+    fn visit(nodes: &[Node], path: &Choices, out: &mut Vec<Choices>) {
+        for node in nodes {
+            if let Node::Choice(choice) = node {
+                for (idx, alternative) in choice.alternatives.iter().enumerate() {
+                    let mut path = path.clone();
+                    path.insert(choice.id, idx);
+                    if idx != choice.default {
+                        out.push(path.clone());
+                    }
+                    visit(alternative, &path, out);
+                }
+            }
+        }
+    }
+
+    /// Returns the choices taking both `a` and `b`, if they can be taken together.
+    fn merge(a: &Choices, b: &Choices) -> Option<Choices> {
+        let mut both = a.clone();
+        for (id, idx) in b {
+            if *both.entry(*id).or_insert(*idx) != *idx {
+                return None;
+            }
+        }
+        Some(both)
+    }
+
+    let mut alternatives = vec![];
+    visit(nodes, &Choices::new(), &mut alternatives);
+    let pairs = alternatives.iter().enumerate().flat_map(|(idx, a)| {
+        alternatives[idx + 1..]
+            .iter()
+            .filter_map(move |b| merge(a, b))
+    });
+
+    let mut out = vec![Choices::new()];
+    for choices in alternatives.iter().cloned().chain(pairs) {
+        if !out.contains(&choices) {
+            out.push(choices);
+        }
+    }
+    out
+}
+
+/// An argument of a rendered query
+struct RenderedArg<'a> {
+    witness: &'a Ident,
+    expr: &'a Expr,
+    list: bool,
+}
+
+impl<'a> RenderedArg<'a> {
+    /// Returns a dummy of the type bound to the placeholder of this argument.
+    /// On postgres, a list is bound as an array, on mysql a single element is compared against.
+    fn dummy<B: Backend>(&self) -> TokenStream {
+        let witness = self.witness;
+        let dummy = quote_spanned!(self.expr.span() => ormx::exports::dummy(&#witness));
+        match self.list && B::LIST_AS_ARRAY {
+            true => quote_spanned!(self.expr.span() => &[#dummy][..]),
+            false => dummy,
+        }
+    }
+
+    /// Returns `NULL` of the type bound to the placeholder of this argument. The type is not checked
+    /// by sqlx (`as _`), since this is only used by the query mapping the rows, whose arguments
+    /// are replaced before it is executed.
+    fn null<B: Backend>(&self) -> TokenStream {
+        let witness = self.witness;
+        match self.list && B::LIST_AS_ARRAY {
+            true => quote!(ormx::exports::null_list(&#witness) as _),
+            false => quote!(ormx::exports::null(&#witness) as _),
+        }
+    }
+}
+
+/// Renders the SQL and the arguments of the query taking the given alternatives.
+/// A list is rendered like at runtime, but with a single placeholder on mysql, which has the same
+/// type as the placeholders of all elements.
+fn render<'a, B: Backend>(nodes: &'a [Node], choices: &Choices) -> (String, Vec<RenderedArg<'a>>) {
+    fn visit<'a, B: Backend>(
+        nodes: &'a [Node],
+        choices: &Choices,
+        bindings: &mut B::Bindings,
+        sql: &mut Vec<String>,
+        args: &mut Vec<RenderedArg<'a>>,
+    ) {
+        for node in nodes {
+            match node {
+                Node::Sql(fragment) => sql.push(fragment.clone()),
+                Node::Arg(witness, expr) => {
+                    sql.push(bindings.next().unwrap().into_owned());
+                    args.push(RenderedArg {
+                        witness,
+                        expr,
+                        list: false,
+                    });
+                }
+                Node::List(witness, expr) => {
+                    let placeholder = bindings.next().unwrap();
+                    sql.push(match B::LIST_AS_ARRAY {
+                        true => format!("= ANY({})", placeholder),
+                        false => format!("IN ({})", placeholder),
+                    });
+                    args.push(RenderedArg {
+                        witness,
+                        expr,
+                        list: true,
+                    });
+                }
+                Node::Choice(choice) => {
+                    let idx = choices.get(&choice.id).copied().unwrap_or(choice.default);
+                    visit::<B>(&choice.alternatives[idx], choices, bindings, sql, args);
+                }
+            }
+        }
+    }
+
+    let (mut sql, mut args) = (vec![], vec![]);
+    let mut bindings = B::Bindings::default();
+    visit::<B>(nodes, choices, &mut bindings, &mut sql, &mut args);
+    (sql.join(" "), args)
+}
+
+/// Tracks whether the SQL of a query is within the list of columns it returns, which starts with
+/// `SELECT` or `RETURNING`, and ends with `FROM`, `UNION`, `INTERSECT` or `EXCEPT`.
+/// Subqueries, string literals and quoted identifiers are skipped.
+#[derive(Clone, Default)]
+struct ColumnScanner {
+    depth: usize,
+    quote: Option<char>,
+    within: bool,
+}
+
+impl ColumnScanner {
+    /// Scans `sql`, returning true if it starts a list of returned columns.
+    fn scan(&mut self, sql: &str) -> bool {
+        let mut started = false;
+        let mut word = String::new();
+        for c in sql.chars().chain(Some(' ')) {
+            if let Some(quote) = self.quote {
+                if c == quote {
+                    self.quote = None;
+                }
+                continue;
+            }
+            if c.is_alphanumeric() || c == '_' {
+                word.push(c);
+                continue;
+            }
+            started |= self.keyword(&word);
+            word.clear();
+            match c {
+                '\'' | '"' | '`' => self.quote = Some(c),
+                '(' => self.depth += 1,
+                ')' => self.depth = self.depth.saturating_sub(1),
+                _ => (),
+            }
+        }
+        started
+    }
+
+    fn keyword(&mut self, word: &str) -> bool {
+        if self.depth > 0 {
+            return false;
+        }
+        match word.to_ascii_uppercase().as_str() {
+            "SELECT" | "RETURNING" => self.within = true,
+            "FROM" | "UNION" | "INTERSECT" | "EXCEPT" => self.within = false,
+            _ => return false,
+        }
+        self.within
+    }
+}
+
+/// Fails with `message` if a condition is placed within the list of returned columns, or starts
+/// one. `condition` is the outermost condition the nodes are part of.
+fn check_columns(
+    nodes: &[Node],
+    scanner: &mut ColumnScanner,
+    condition: Option<&Fragment>,
+    message: &str,
+) -> Result<(), Error> {
+    let error = |fragment: &Fragment| match fragment {
+        Fragment::Condition(condition) => Error::new_spanned(&condition.pat, message),
+        Fragment::Match(m) => Error::new_spanned(&m.expr, message),
+        _ => unreachable!(),
+    };
+    for node in nodes {
+        match node {
+            Node::Sql(sql) => {
+                let started = scanner.scan(sql);
+                match condition {
+                    Some(condition) if started || scanner.within => return Err(error(condition)),
+                    _ => (),
+                }
+            }
+            Node::Arg(..) | Node::List(..) => (),
+            Node::Choice(choice) => {
+                if scanner.within {
+                    return Err(error(choice.fragment));
+                }
+                let mut default = None;
+                for (idx, alternative) in choice.alternatives.iter().enumerate() {
+                    let mut alternative_scanner = scanner.clone();
+                    let condition = condition.unwrap_or(choice.fragment);
+                    check_columns(
+                        alternative,
+                        &mut alternative_scanner,
+                        Some(condition),
+                        message,
+                    )?;
+                    if idx == choice.default {
+                        default = Some(alternative_scanner);
+                    }
+                }
+                *scanner = default.unwrap();
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use proc_macro2::TokenStream;
+    use quote::quote;
+
+    use super::*;
+    use crate::backend::Implementation;
+    use crate::query2::Macro;
+
+    fn parse(input: TokenStream) -> ConditionalQuery {
+        ConditionalQuery::parse(input, Macro::Query).unwrap()
+    }
+
+    fn choices(choices: &[(usize, usize)]) -> Choices {
+        choices.iter().copied().collect()
+    }
+
+    fn check(input: TokenStream) -> Result<(), Error> {
+        let query = parse(input);
+        let nodes = lower(&query.fragments, &mut Ids::default());
+        check_columns(
+            &nodes,
+            &mut ColumnScanner::default(),
+            None,
+            RECORD_BY_POSITION,
+        )
+    }
+
+    #[test]
+    fn checks_every_pair_of_alternatives() {
+        let query = parse(quote! {
+            "SELECT id FROM users WHERE TRUE"
+            Some(a) = a => { "AND a =" ?(a) }
+            Some(b) = b => { "AND b =" ?(b) }
+            Some(c) = c => { "AND c =" ?(c) }
+        });
+        let nodes = lower(&query.fragments, &mut Ids::default());
+        assert_eq!(
+            checks(&nodes),
+            vec![
+                choices(&[]),
+                choices(&[(0, 0)]),
+                choices(&[(1, 0)]),
+                choices(&[(2, 0)]),
+                choices(&[(0, 0), (1, 0)]),
+                choices(&[(0, 0), (2, 0)]),
+                choices(&[(1, 0), (2, 0)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_nested_conditions_and_matches() {
+        let query = parse(quote! {
+            "SELECT id FROM users"
+            Some(a) = a => {
+                "WHERE a =" ?(a)
+                Some(b) = b => { "AND b =" ?(b) }
+            }
+            match order {
+                Order::A => { "ORDER BY a" },
+                Order::B => { "ORDER BY b" },
+                Order::C => { "ORDER BY c" },
+            }
+        });
+        let nodes = lower(&query.fragments, &mut Ids::default());
+        // the arms of a match are never combined with each other
+        assert_eq!(
+            checks(&nodes),
+            vec![
+                choices(&[]),
+                choices(&[(0, 0)]),
+                choices(&[(0, 0), (1, 0)]),
+                choices(&[(2, 1)]),
+                choices(&[(2, 2)]),
+                choices(&[(0, 0), (2, 1)]),
+                choices(&[(0, 0), (2, 2)]),
+                choices(&[(0, 0), (1, 0), (2, 1)]),
+                choices(&[(0, 0), (1, 0), (2, 2)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_grow_quadratically() {
+        let conditions = (0..10usize).map(|idx| {
+            let ident = format_ident!("c{}", idx);
+            quote!(Some(#ident) = #ident => { "AND c =" ?(#ident) })
+        });
+        let query = parse(quote!("SELECT id FROM users WHERE TRUE" #( #conditions )*));
+        let nodes = lower(&query.fragments, &mut Ids::default());
+        assert_eq!(checks(&nodes).len(), 1 + 10 + 45);
+    }
+
+    #[test]
+    fn render_alternatives() {
+        let query = parse(quote! {
+            "SELECT id FROM users"
+            Some(ids) = ids => {
+                "WHERE id" ?*(ids)
+                "AND name =" ?(name)
+            } else {
+                "WHERE name =" ?(name)
+            }
+        });
+        let nodes = lower(&query.fragments, &mut Ids::default());
+        let (sql, args) = render::<Implementation>(&nodes, &choices(&[]));
+        assert_eq!(args.len(), 1);
+        assert!(!args[0].list);
+        let (taken, args) = render::<Implementation>(&nodes, &choices(&[(0, 0)]));
+        assert_eq!(
+            args.iter().map(|arg| arg.list).collect::<Vec<_>>(),
+            [true, false]
+        );
+
+        #[cfg(feature = "postgres")]
+        {
+            assert_eq!(sql, "SELECT id FROM users WHERE name = $1");
+            assert_eq!(
+                taken,
+                "SELECT id FROM users WHERE id = ANY($1) AND name = $2"
+            );
+        }
+        #[cfg(feature = "mysql")]
+        {
+            assert_eq!(sql, "SELECT id FROM users WHERE name = ?");
+            assert_eq!(taken, "SELECT id FROM users WHERE id IN (?) AND name = ?");
+        }
+    }
+
+    #[test]
+    fn scan_columns() {
+        let mut scanner = ColumnScanner::default();
+        assert!(scanner.scan("SELECT id,"));
+        assert!(scanner.within);
+        assert!(!scanner.scan("name FROM users"));
+        assert!(!scanner.within);
+
+        let mut scanner = ColumnScanner::default();
+        scanner.scan("SELECT (SELECT 1 FROM other) AS one, 'FROM' AS two, \"from\",");
+        assert!(scanner.within);
+
+        let mut scanner = ColumnScanner::default();
+        assert!(scanner.scan("UPDATE users SET name = 'x' RETURNING"));
+        assert!(scanner.within);
+
+        let mut scanner = ColumnScanner::default();
+        assert!(scanner.scan("select id from users union"));
+        assert!(!scanner.within);
+        assert!(!scanner.scan("WHERE id IN (SELECT id FROM other)"));
+        assert!(!scanner.within);
+    }
+
+    #[test]
+    fn conditions_after_columns() {
+        check(quote! {
+            "SELECT id, (SELECT 1 FROM other) AS one FROM users"
+            Some(a) = a => {
+                "WHERE id IN (SELECT id FROM other WHERE a =" ?(a) ")"
+                Some(b) = b => { "AND b =" ?(b) }
+            }
+            match order {
+                Order::A => { "ORDER BY a" },
+                Order::B => { "ORDER BY b" },
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn conditions_within_columns() {
+        let err = check(quote! {
+            "SELECT id"
+            Some(a) = a => { ", a" }
+            "FROM users"
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), RECORD_BY_POSITION);
+
+        // the condition of the outermost condition is reported
+        check(quote! {
+            "SELECT id FROM users"
+            Some(a) = a => {
+                "WHERE a =" ?(a)
+                Some(b) = b => { "UNION SELECT id FROM other" }
+            }
+        })
+        .unwrap_err();
+
+        check(quote! {
+            "SELECT id FROM users"
+            match a {
+                A::X => { "" },
+                A::Y => { "RETURNING id" },
+            }
+        })
+        .unwrap_err();
+    }
+}
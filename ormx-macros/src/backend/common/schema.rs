@@ -0,0 +1,228 @@
+use itertools::Itertools;
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::backend::Backend;
+use crate::table::{ForeignKey, Table, TableField};
+use crate::utils::split_schema;
+
+pub fn impl_schema<B: Backend>(table: &Table<B>) -> TokenStream {
+    if !table.ddl {
+        return quote!();
+    }
+    let (mut inline, mut statements, mut add_indexes) = (vec![], vec![], vec![]);
+    for index in &table.indexes {
+        match B::index_sql(table, index) {
+            Ok(sql) if B::INLINE_INDEXES => {
+                add_indexes.push(format!(
+                    "ALTER TABLE {} ADD {}",
+                    table.qualified_table(),
+                    sql
+                ));
+                inline.push(sql);
+            }
+            Ok(sql) => {
+                add_indexes.push(sql.clone());
+                statements.push(sql);
+            }
+            Err(err) => return err.to_compile_error(),
+        }
+    }
+    let table_ident = &table.ident;
+    let column_definitions = table.fields.iter().map(|field| {
+        let mut sql = Sql::default();
+        column_definition(field, &mut sql);
+        sql.into_tokens()
+    });
+    let prefix = format!("CREATE TABLE IF NOT EXISTS {} (", table.qualified_table());
+    let comments = comments_sql(table);
+    let constraints = table_constraints(table, &inline);
+
+    quote! {
+        impl ormx::Schema for #table_ident {
+            fn create_table_sql() -> String {
+                [
+                    #prefix,
+                    &<Self as ormx::Schema>::column_definitions().join(", "),
+                    #constraints,
+                ]
+                .concat()
+            }
+
+            fn create_indexes_sql() -> Vec<String> {
+                vec![ #( String::from(#statements) ),* ]
+            }
+
+            fn create_comments_sql() -> Vec<String> {
+                vec![ #( String::from(#comments) ),* ]
+            }
+
+            fn column_definitions() -> Vec<String> {
+                vec![ #( #column_definitions ),* ]
+            }
+
+            fn add_indexes_sql() -> Vec<String> {
+                vec![ #( String::from(#add_indexes) ),* ]
+            }
+        }
+    }
+}
+
+/// Renders everything following the column definitions in `CREATE TABLE`, starting with the
+/// primary key.
+fn table_constraints<B: Backend>(table: &Table<B>, indexes: &[String]) -> String {
+    let primary_key = table
+        .primary_key()
+        .iter()
+        .map(|field| field.column())
+        .join(", ");
+    let mut sql = format!(", PRIMARY KEY ({})", primary_key);
+    for field in &table.fields {
+        if let Some(references) = &field.references {
+            sql.push_str(&format!(
+                ", FOREIGN KEY ({}) {}",
+                field.column(),
+                references_sql::<B>(references)
+            ));
+        }
+    }
+    for index in indexes {
+        sql.push_str(&format!(", {}", index));
+    }
+    sql.push(')');
+    if let (true, Some(comment)) = (B::INLINE_COMMENTS, &table.comment) {
+        sql.push_str(&format!(" COMMENT = {}", B::string_literal(comment)));
+    }
+    sql
+}
+
+/// Renders the `REFERENCES` clause of a foreign key. The referenced table may be qualified by its
+/// schema, and is quoted like the table itself.
+fn references_sql<B: Backend>(references: &ForeignKey) -> String {
+    let (schema, table) = split_schema(None, references.table.clone());
+    let mut sql = format!(
+        "REFERENCES {} ({})",
+        B::qualified_name(schema.as_deref(), &table),
+        B::quote_ident(&references.column)
+    );
+    if let Some(action) = references.on_delete {
+        sql.push_str(&format!(" ON DELETE {}", action.sql()));
+    }
+    if let Some(action) = references.on_update {
+        sql.push_str(&format!(" ON UPDATE {}", action.sql()));
+    }
+    sql
+}
+
+/// Renders the `COMMENT ON` statements for the doc comments of the table and its fields, unless
+/// they are defined within `CREATE TABLE`.
+fn comments_sql<B: Backend>(table: &Table<B>) -> Vec<String> {
+    if B::INLINE_COMMENTS {
+        return vec![];
+    }
+    let table_comment = table.comment.iter().map(|comment| {
+        format!(
+            "COMMENT ON TABLE {} IS {}",
+            table.qualified_table(),
+            B::string_literal(comment)
+        )
+    });
+    let column_comments = table.fields.iter().filter_map(|field| {
+        let comment = field.comment.as_ref()?;
+        Some(format!(
+            "COMMENT ON COLUMN {}.{} IS {}",
+            table.qualified_table(),
+            field.column(),
+            B::string_literal(comment)
+        ))
+    });
+    table_comment.chain(column_comments).collect()
+}
+
+/// Renders the definition of a column, e.g. `email VARCHAR(128) NOT NULL`.
+fn column_definition<B: Backend>(field: &TableField<B>, sql: &mut Sql) {
+    sql.push_str(&format!("{} ", field.column()));
+    sql.push_expr(field.sql_type());
+    if !field.is_nullable() {
+        sql.push_str(" NOT NULL");
+    }
+    if let Some(default) = &field.default_expr {
+        sql.push_str(&format!(" DEFAULT {}", default));
+    }
+    if field.auto_increment {
+        sql.push_str(&format!(" {}", B::AUTO_INCREMENT));
+    }
+    if let Some(identity) = field.identity {
+        sql.push_str(&format!(" {}", identity.sql()));
+    }
+    if let (true, Some(comment)) = (B::INLINE_COMMENTS, &field.comment) {
+        sql.push_str(&format!(" COMMENT {}", B::string_literal(comment)));
+    }
+}
+
+/// SQL containing expressions which are only known at runtime, like the types of columns inferred
+/// using `ormx::SqlType`.
+#[derive(Default)]
+struct Sql {
+    parts: Vec<TokenStream>,
+    literal: String,
+}
+
+impl Sql {
+    fn push_str(&mut self, sql: &str) {
+        self.literal.push_str(sql);
+    }
+
+    /// Appends an expression evaluating to a `&str`.
+    fn push_expr(&mut self, expr: TokenStream) {
+        self.flush();
+        self.parts.push(expr);
+    }
+
+    fn flush(&mut self) {
+        if !self.literal.is_empty() {
+            let literal = std::mem::take(&mut self.literal);
+            self.parts.push(quote!(#literal));
+        }
+    }
+
+    /// Generates an expression evaluating to a `String`.
+    fn into_tokens(mut self) -> TokenStream {
+        self.flush();
+        let parts = &self.parts;
+        quote!([ #( #parts ),* ].concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::references_sql;
+    use crate::backend::{Backend, Implementation};
+    use crate::table::{ForeignKey, ReferentialAction};
+
+    fn references(table: &str, column: &str) -> ForeignKey {
+        ForeignKey {
+            table: table.to_owned(),
+            column: column.to_owned(),
+            on_delete: Some(ReferentialAction::Cascade),
+            on_update: None,
+        }
+    }
+
+    #[test]
+    fn quote_referenced_table() {
+        let quoted = |ident: &str| Implementation::quoted(ident);
+        assert_eq!(
+            references_sql::<Implementation>(&references("users", "id")),
+            "REFERENCES users (id) ON DELETE CASCADE"
+        );
+        assert_eq!(
+            references_sql::<Implementation>(&references("auth.order", "select")),
+            format!(
+                "REFERENCES auth.{} ({}) ON DELETE CASCADE",
+                quoted("order"),
+                quoted("select")
+            )
+        );
+    }
+}
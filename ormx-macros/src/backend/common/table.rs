@@ -1,8 +1,9 @@
 use proc_macro2::TokenStream;
-use quote::{TokenStreamExt, quote};
+use quote::quote;
 
 use crate::backend::Backend;
 use crate::table::Table;
+use crate::utils::{query, RUNTIME_QUERIES};
 
 pub fn impl_table<B: Backend>(table: &Table<B>) -> TokenStream {
     let table_ident = &table.ident;
@@ -14,6 +15,7 @@ pub fn impl_table<B: Backend>(table: &Table<B>) -> TokenStream {
     let stream_all = stream_all(table, &column_list);
     let stream_all_paginated = stream_all_paginated::<B>(table, &column_list);
     let update = update::<B>(table);
+    let update_and_refresh = update_and_refresh::<B>(table);
     let delete = delete::<B>(table);
 
     quote! {
@@ -26,6 +28,7 @@ pub fn impl_table<B: Backend>(table: &Table<B>) -> TokenStream {
             #stream_all
             #stream_all_paginated
             #update
+            #update_and_refresh
             #delete
         }
     }
@@ -36,10 +39,11 @@ fn get<B: Backend>(table: &Table<B>, column_list: &str) -> TokenStream {
     let get_sql = format!(
         "SELECT {} FROM {} WHERE {} = {}",
         column_list,
-        table.table,
+        table.qualified_table(),
         table.id.column(),
         B::Bindings::default().next().unwrap()
     );
+    let query = table.query_as_self(&get_sql, &[quote!(id)]);
 
     quote! {
         fn get<'a, 'c: 'a>(
@@ -47,7 +51,7 @@ fn get<B: Backend>(table: &Table<B>, column_list: &str) -> TokenStream {
             id: Self::Id,
         ) -> #box_future<'a, sqlx::Result<Self>> {
             Box::pin(async move {
-                sqlx::query_as!(Self, #get_sql, id)
+                #query
                     .fetch_one(db)
                     .await
             })
@@ -57,9 +61,48 @@ fn get<B: Backend>(table: &Table<B>, column_list: &str) -> TokenStream {
 
 fn update<B: Backend>(table: &Table<B>) -> TokenStream {
     let box_future = crate::utils::box_future();
+    let (update_sql, arguments) = update_sql::<B>(table);
+    let query = query(&update_sql, &arguments);
+
+    quote! {
+        fn update<'a, 'c: 'a>(
+            &'a self,
+            db: impl sqlx::Executor<'c, Database = ormx::Db> + 'a,
+        ) -> #box_future<'a, sqlx::Result<()>> {
+            Box::pin(async move {
+                #query
+                    .execute(db)
+                    .await?;
+                Ok(())
+            })
+        }
+    }
+}
+
+fn update_and_refresh<B: Backend>(table: &Table<B>) -> TokenStream {
+    let box_future = crate::utils::box_future();
+    let (update_sql, arguments) = update_sql::<B>(table);
+    let write = super::write_and_refresh(table, &update_sql, &arguments, quote!(&mut *_conn));
+
+    quote! {
+        fn update_and_refresh<'a, 'c: 'a>(
+            &'a mut self,
+            db: impl sqlx::Acquire<'c, Database = ormx::Db> + Send + 'a,
+        ) -> #box_future<'a, sqlx::Result<()>> {
+            Box::pin(async move {
+                let mut _conn = sqlx::Acquire::acquire(db).await?;
+                #write
+                Ok(())
+            })
+        }
+    }
+}
+
+/// Returns the statement updating all updatable fields of `self`, and its arguments.
+fn update_sql<B: Backend>(table: &Table<B>) -> (String, Vec<TokenStream>) {
     let mut bindings = B::Bindings::default();
     let mut assignments = vec![];
-    for field in table.fields_except_id() {
+    for field in table.updatable_fields() {
         let fragment = format!("{} = {}", field.column(), bindings.next().unwrap());
         assignments.push(fragment);
     }
@@ -67,48 +110,30 @@ fn update<B: Backend>(table: &Table<B>) -> TokenStream {
 
     let update_sql = format!(
         "UPDATE {} SET {} WHERE {} = {}",
-        table.table,
+        table.qualified_table(),
         assignments,
         table.id.column(),
         bindings.next().unwrap()
     );
     let id_argument = &table.id.field;
-    let other_arguments = table.fields_except_id().map(|field| {
-        let ident = &field.field;
-        let mut out = quote!(self.#ident);
-
-        if field.custom_type {
-            let ty = &field.ty;
-            out.append_all(quote!(as #ty))
-        }
-
-        out
-    });
-
-    quote! {
-        fn update<'a, 'c: 'a>(
-            &'a self,
-            db: impl sqlx::Executor<'c, Database = ormx::Db> + 'a,
-        ) -> #box_future<'a, sqlx::Result<()>> {
-            Box::pin(async move {
-                sqlx::query!(#update_sql, #( #other_arguments, )* self.#id_argument)
-                    .execute(db)
-                    .await?;
-                Ok(())
-            })
-        }
-    }
+    let mut arguments = table
+        .updatable_fields()
+        .map(|field| field.arg(quote!(self)))
+        .collect::<Vec<_>>();
+    arguments.push(quote!(self.#id_argument));
+    (update_sql, arguments)
 }
 
 fn stream_all<B: Backend>(table: &Table<B>, column_list: &str) -> TokenStream {
     let box_stream = crate::utils::box_stream();
-    let all_sql = format!("SELECT {} FROM {}", column_list, table.table);
+    let all_sql = format!("SELECT {} FROM {}", column_list, table.qualified_table());
+    let query = table.query_as_self(&all_sql, &[]);
 
     quote! {
         fn stream_all<'a, 'c: 'a>(
             db: impl sqlx::Executor<'c, Database = ormx::Db> + 'a,
         ) -> #box_stream<'a, sqlx::Result<Self>> {
-            sqlx::query_as!(Self, #all_sql)
+            #query
                 .fetch(db)
         }
     }
@@ -120,10 +145,20 @@ fn stream_all_paginated<B: Backend>(table: &Table<B>, column_list: &str) -> Toke
     let all_sql = format!(
         "SELECT {} FROM {} LIMIT {} OFFSET {}",
         column_list,
-        table.table,
+        table.qualified_table(),
         bindings.next().unwrap(),
         bindings.next().unwrap()
     );
+    // the stream outlives this function, so the runtime query can't borrow `limit` and `offset`
+    let query = match RUNTIME_QUERIES {
+        true => quote! {
+            sqlx::query::<ormx::Db>(#all_sql)
+                .bind(limit)
+                .bind(offset)
+                .try_map(|row: <ormx::Db as sqlx::Database>::Row| Self::__ormx_from_row(&row))
+        },
+        false => table.query_as_self(&all_sql, &[quote!(limit), quote!(offset)]),
+    };
 
     quote! {
         fn stream_all_paginated<'a, 'c: 'a>(
@@ -131,7 +166,7 @@ fn stream_all_paginated<B: Backend>(table: &Table<B>, column_list: &str) -> Toke
             offset: i64,
             limit: i64,
         ) -> #box_stream<'a, sqlx::Result<Self>> {
-            sqlx::query_as!(Self, #all_sql, limit, offset)
+            #query
                 .fetch(db)
         }
     }
@@ -142,7 +177,7 @@ fn delete<B: Backend>(table: &Table<B>) -> TokenStream {
     let id_ty = &table.id.ty;
     let delete_sql = format!(
         "DELETE FROM {} WHERE {} = {}",
-        table.table,
+        table.qualified_table(),
         table.id.column(),
         B::Bindings::default().next().unwrap()
     );
@@ -152,6 +187,7 @@ fn delete<B: Backend>(table: &Table<B>) -> TokenStream {
     let result_import = quote!(sqlx::postgres::PgQueryResult);
     #[cfg(feature = "sqlite")]
     let result_import = quote!(sqlx::sqlite::SqliteQueryResult);
+    let query = query(&delete_sql, &[quote!(id)]);
 
     quote! {
         fn delete_row<'a, 'c: 'a>(
@@ -161,7 +197,7 @@ fn delete<B: Backend>(table: &Table<B>) -> TokenStream {
             use #result_import;
 
             Box::pin(async move {
-                let result = sqlx::query!(#delete_sql, id)
+                let result = #query
                     .execute(db)
                     .await?;
                 if result.rows_affected() == 0 {
@@ -173,3 +209,58 @@ fn delete<B: Backend>(table: &Table<B>) -> TokenStream {
         }
     }
 }
+
+/// Generates a hidden function reading a row selected using the column list of the table by the
+/// names of its columns, which is used by conditional queries splicing the columns of the table.
+/// With `runtime-queries`, the rows of every query returning the table are read using it, so the
+/// table doesn't have to implement `FromRow`. Otherwise, the hidden struct the macros read rows into
+/// is generated, if needed.
+pub fn impl_from_row<B: Backend>(table: &Table<B>) -> TokenStream {
+    let table_ident = &table.ident;
+    let vis = &table.vis;
+    let skipped = table.skipped_fields();
+    let fields = Table::init_fields(table.fields.iter(), |field| {
+        let label = field.select_label();
+        let ty = field.row_ty();
+        field.unwrap_json(quote!(sqlx::Row::try_get::<#ty, _>(row, #label)?))
+    });
+    let row_struct = match RUNTIME_QUERIES {
+        true => quote!(),
+        false => row_struct(table),
+    };
+
+    quote! {
+        impl #table_ident {
+            #[doc(hidden)]
+            #vis fn __ormx_from_row(row: &<ormx::Db as sqlx::Database>::Row) -> sqlx::Result<Self> {
+                Ok(Self {
+                    #fields
+                    #skipped
+                })
+            }
+        }
+
+        #row_struct
+    }
+}
+
+/// Generates the hidden struct rows are read into by `sqlx::query_as!` if some fields are
+/// skipped, flattened or stored as JSON.
+fn row_struct<B: Backend>(table: &Table<B>) -> TokenStream {
+    if !table.needs_row_struct() {
+        return quote!();
+    }
+    let vis = &table.vis;
+    let row_ident = table.row_ident();
+    let fields = table.fields.iter().map(|field| {
+        let (ident, ty) = (&field.field, field.row_ty());
+        quote!(#vis #ident: #ty)
+    });
+    // conditional queries splicing the columns of the table read rows into it, too
+    quote! {
+        #[doc(hidden)]
+        #vis struct #row_ident {
+            #( #fields, )*
+        }
+    }
+}
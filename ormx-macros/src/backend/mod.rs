@@ -2,7 +2,9 @@ use std::borrow::Cow;
 
 use proc_macro2::TokenStream;
 
+use crate::attrs::Index;
 use crate::patch::Patch;
+use crate::query2::ConditionalQuery;
 use crate::table::Table;
 
 mod common;
@@ -20,11 +22,20 @@ compile_error!("sqlite is currently not supported");
 
 pub trait Backend: Sized + Clone {
     const QUOTE: char;
+    /// Column option generating the values of an integer column
+    const AUTO_INCREMENT: &'static str;
+    /// Whether identity columns (`#[ormx(identity = ..)]`) are supported
+    const IDENTITY: bool;
+    /// Whether `UPDATE` supports `RETURNING`
+    const RETURNING: bool;
     /// TODO: benchmark HashSet vs linear search
     const RESERVED_IDENTS: &'static [&'static str];
 
     type Bindings: Iterator<Item = Cow<'static, str>> + Default;
 
+    /// Render a comparison of a column with a placeholder which treats two NULL values as equal
+    fn null_safe_eq(column: &str, placeholder: &str) -> String;
+
     /// Generate an `impl <Table>` block, containing getter methods
     fn impl_getters(table: &Table<Self>) -> TokenStream {
         common::getters::<Self>(table)
@@ -40,6 +51,94 @@ pub trait Backend: Sized + Clone {
         common::impl_table::<Self>(table)
     }
 
+    /// Generate a hidden function reading rows by the names of their columns, and the hidden
+    /// struct rows are read into if some fields are skipped and queries are checked at compile time
+    fn impl_from_row(table: &Table<Self>) -> TokenStream {
+        common::impl_from_row::<Self>(table)
+    }
+
+    /// Generate an `impl TableMeta for <Table>` block and typed column markers
+    fn impl_table_meta(table: &Table<Self>) -> TokenStream {
+        common::impl_table_meta::<Self>(table)
+    }
+
+    /// Generate an `impl Schema for <Table>` block if the table is annotated with `#[ormx(ddl)]`
+    fn impl_schema(table: &Table<Self>) -> TokenStream {
+        common::impl_schema::<Self>(table)
+    }
+
+    /// Whether indexes are defined within `CREATE TABLE` instead of by statements of their own
+    const INLINE_INDEXES: bool;
+
+    /// Whether comments are defined within `CREATE TABLE` instead of by statements of their own
+    const INLINE_COMMENTS: bool;
+
+    /// Whether unquoted identifiers are folded to lowercase
+    const LOWERCASE_IDENTS: bool;
+
+    /// Whether lists (`?*(..)`) are bound as a single array instead of one parameter per element
+    const LIST_AS_ARRAY: bool;
+
+    /// Render a string literal
+    fn string_literal(value: &str) -> String {
+        format!("'{}'", value.replace('\'', "''"))
+    }
+
+    /// Whether an identifier has to be quoted to be used within a query
+    fn needs_quotes(ident: &str) -> bool {
+        Self::RESERVED_IDENTS.contains(&&*ident.to_uppercase())
+    }
+
+    /// Render an identifier, quoting it if required. Identifiers which are already quoted are
+    /// taken as they are.
+    fn quote_ident(ident: &str) -> Cow<'_, str> {
+        if !ident.starts_with(Self::QUOTE) && Self::needs_quotes(ident) {
+            Self::quoted(ident).into()
+        } else {
+            Cow::Borrowed(ident)
+        }
+    }
+
+    /// Render a column named using `#[ormx(rename_all = ..)]`, which is also quoted if it would
+    /// lose its case otherwise
+    fn quote_renamed(ident: &str) -> Cow<'_, str> {
+        if Self::LOWERCASE_IDENTS && ident.chars().any(|c| c.is_uppercase()) {
+            Self::quoted(ident).into()
+        } else {
+            Self::quote_ident(ident)
+        }
+    }
+
+    /// Quote an identifier, escaping the quote character within it
+    fn quoted(ident: &str) -> String {
+        let escaped = ident.replace(Self::QUOTE, &format!("{0}{0}", Self::QUOTE));
+        format!("{}{}{}", Self::QUOTE, escaped, Self::QUOTE)
+    }
+
+    /// Returns the name the database stores an identifier under, given the identifier as it is
+    /// used within queries
+    fn stored_name(ident: &str) -> String {
+        let quoted = ident
+            .strip_prefix(Self::QUOTE)
+            .and_then(|ident| ident.strip_suffix(Self::QUOTE));
+        match quoted {
+            Some(quoted) => quoted.to_owned(),
+            None if Self::LOWERCASE_IDENTS => ident.to_lowercase(),
+            None => ident.to_owned(),
+        }
+    }
+
+    /// Render the name of a table, qualified by its schema if given
+    fn qualified_name(schema: Option<&str>, table: &str) -> String {
+        match schema {
+            Some(schema) => format!("{}.{}", Self::quote_ident(schema), Self::quote_ident(table)),
+            None => Self::quote_ident(table).into_owned(),
+        }
+    }
+
+    /// Render an index of a table annotated with `#[ormx(ddl)]`
+    fn index_sql(table: &Table<Self>, index: &Index) -> syn::Result<String>;
+
     /// Implement [Insert] for the helper struct for inserting
     fn impl_insert(table: &Table<Self>) -> TokenStream;
 
@@ -52,4 +151,52 @@ pub trait Backend: Sized + Clone {
     fn impl_patch(patch: &Patch) -> TokenStream {
         common::impl_patch::<Self>(patch)
     }
+
+    /// Expand `conditional_query_as!` and its variants
+    fn impl_conditional_query(query: &ConditionalQuery) -> TokenStream {
+        common::conditional_query::<Self>(query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backend, Implementation};
+
+    fn quoted(ident: &str) -> String {
+        let quote = Implementation::QUOTE;
+        format!("{}{}{}", quote, ident, quote)
+    }
+
+    #[test]
+    fn quote_reserved_idents() {
+        assert_eq!(Implementation::quote_ident("order"), quoted("order"));
+        assert_eq!(Implementation::quote_ident("users"), "users");
+        assert_eq!(
+            Implementation::quote_ident(&quoted("order")),
+            quoted("order")
+        );
+        assert_eq!(
+            Implementation::qualified_name(Some("auth"), "order"),
+            format!("auth.{}", quoted("order"))
+        );
+    }
+
+    #[test]
+    fn escape_quotes() {
+        let quote = Implementation::QUOTE;
+        assert_eq!(
+            Implementation::quoted(&format!("a{}b", quote)),
+            quoted(&format!("a{0}{0}b", quote))
+        );
+    }
+
+    #[test]
+    fn quote_renamed_idents() {
+        let renamed = Implementation::quote_renamed("userId");
+        match Implementation::LOWERCASE_IDENTS {
+            true => assert_eq!(renamed, quoted("userId")),
+            false => assert_eq!(renamed, "userId"),
+        }
+        assert_eq!(Implementation::quote_renamed("user_id"), "user_id");
+    }
 }
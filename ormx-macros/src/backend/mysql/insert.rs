@@ -1,11 +1,12 @@
 use itertools::Itertools;
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use quote::quote;
 use syn::Ident;
 
 use crate::table::Table;
+use crate::utils::{query, record_field};
 
-use super::MySqlBackend;
+use super::{MySqlBackend, MySqlBindings};
 
 pub fn impl_insert(table: &Table<MySqlBackend>) -> TokenStream {
     let insert_ident = match &table.insertable {
@@ -19,9 +20,9 @@ pub fn impl_insert(table: &Table<MySqlBackend>) -> TokenStream {
     let id_ident = &table.id.field;
     let table_ident = &table.ident;
     let box_future = quote!(ormx::exports::futures::future::BoxFuture);
-    let insert_field_idents = insert_fields
-        .iter()
-        .map(|field| &field.field)
+    let insert_field_idents = Table::struct_fields(insert_fields.iter().copied())
+        .into_iter()
+        .map(|(ident, _)| ident)
         .collect::<Vec<&Ident>>();
     let default_field_idents = default_fields
         .iter()
@@ -29,9 +30,9 @@ pub fn impl_insert(table: &Table<MySqlBackend>) -> TokenStream {
         .collect::<Vec<&Ident>>();
     let insert_sql = format!(
         "INSERT INTO {} ({}) VALUES ({})",
-        table.table,
+        table.qualified_table(),
         insert_fields.iter().map(|field| field.column()).join(", "),
-        std::iter::repeat("?").take(insert_fields.len()).join(", ")
+        MySqlBindings.take(insert_fields.len()).join(", ")
     );
     let query_default_sql = format!(
         "SELECT {} FROM {} WHERE {} = ?",
@@ -39,18 +40,40 @@ pub fn impl_insert(table: &Table<MySqlBackend>) -> TokenStream {
             .iter()
             .map(|field| field.fmt_for_select())
             .join(", "),
-        table.table,
+        table.qualified_table(),
         table.id.column()
     );
     let query_default = if default_fields.is_empty() {
         quote!()
     } else {
+        let query = query(&query_default_sql, &[quote!(_id)]);
         quote! {
-            let _generated = sqlx::query!(#query_default_sql, _id)
+            let _generated = #query
                 .fetch_one(db)
                 .await?;
         }
     };
+    let default_field_values = default_fields.iter().map(|field| {
+        let ty = &field.ty;
+        record_field(quote!(_generated), &field.field, &quote!(#ty))
+    });
+
+    let insert_field_exprs = insert_fields
+        .iter()
+        .map(|field| field.arg(quote!(self)))
+        .collect::<Vec<TokenStream>>();
+    let skipped = table.skipped_fields();
+    let insert = query(&insert_sql, &insert_field_exprs);
+    let last_insert_id = query("SELECT LAST_INSERT_ID() AS id", &[]);
+    let id = record_field(
+        quote! {
+            #last_insert_id
+                .fetch_one(db as &mut sqlx::MySqlConnection)
+                .await?
+        },
+        &Ident::new("id", Span::call_site()),
+        &quote!(u64),
+    );
 
     quote! {
         impl ormx::Insert for #insert_ident {
@@ -59,22 +82,20 @@ pub fn impl_insert(table: &Table<MySqlBackend>) -> TokenStream {
             fn insert(
                 self,
                 db: &mut sqlx::MySqlConnection,
-            ) -> #box_future<sqlx::Result<Self::Table>> {
+            ) -> #box_future<'_, sqlx::Result<Self::Table>> {
                 Box::pin(async move {
-                    sqlx::query!(#insert_sql, #( self.#insert_field_idents, )*)
+                    #insert
                         .execute(db as &mut sqlx::MySqlConnection)
                         .await?;
-                    let _id = sqlx::query!("SELECT LAST_INSERT_ID() AS id")
-                        .fetch_one(db as &mut sqlx::MySqlConnection)
-                        .await?
-                        .id;
+                    let _id = #id;
 
                     #query_default
 
                     Ok(Self::Table {
                         #id_ident: _id as _,
                         #( #insert_field_idents: self.#insert_field_idents, )*
-                        #( #default_field_idents: _generated.#default_field_idents, )*
+                        #( #default_field_idents: #default_field_values, )*
+                        #skipped
                     })
                 })
             }
@@ -0,0 +1,51 @@
+use itertools::Itertools;
+use syn::{Error, Result};
+
+use crate::attrs::Index;
+use crate::backend::mysql::MySqlBackend;
+use crate::backend::Backend;
+use crate::table::Table;
+
+const INDEX_METHODS: &[&str] = &["btree", "hash"];
+
+/// Renders `[UNIQUE | FULLTEXT | SPATIAL] INDEX <name> [USING <method>] (<columns>)`.
+/// Since mysql has no `CREATE INDEX IF NOT EXISTS`, indexes are defined within `CREATE TABLE`.
+pub fn index_sql(table: &Table<MySqlBackend>, index: &Index) -> Result<String> {
+    if index.concurrently {
+        return Err(Error::new(
+            index.span,
+            "concurrently is only supported on postgres",
+        ));
+    }
+    let kind = match (index.unique, index.fulltext, index.spatial) {
+        (false, false, false) => "",
+        (true, false, false) => "UNIQUE ",
+        (false, true, false) => "FULLTEXT ",
+        (false, false, true) => "SPATIAL ",
+        _ => {
+            return Err(Error::new(
+                index.span,
+                "unique, fulltext and spatial are mutually exclusive",
+            ))
+        }
+    };
+    let mut sql = format!(
+        "{}INDEX {}",
+        kind,
+        MySqlBackend::quoted(&table.index_name(index))
+    );
+    if let Some(method) = &index.method {
+        if !INDEX_METHODS.contains(&&*method.to_string()) {
+            let message = format!("expected one of {}", INDEX_METHODS.join(", "));
+            return Err(Error::new(method.span(), message));
+        }
+        sql.push_str(&format!(" USING {}", method.to_string().to_uppercase()));
+    }
+    let columns = index
+        .columns
+        .iter()
+        .map(|column| table.field(column).column())
+        .join(", ");
+    sql.push_str(&format!(" ({})", columns));
+    Ok(sql)
+}
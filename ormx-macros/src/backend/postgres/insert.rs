@@ -5,30 +5,23 @@ use syn::Ident;
 
 use crate::backend::postgres::{PgBackend, PgBindings};
 use crate::table::{Table, TableField};
+use crate::utils::{query, record_field};
 
-fn insert_sql(table: &Table<PgBackend>, insert_fields: &[&TableField<PgBackend>]) -> String {
-    format!(
-        "INSERT INTO {} ({}) VALUES ({}) RETURNING {}",
-        table.table,
-        insert_fields.iter().map(|field| field.column()).join(", "),
-        PgBindings::default().take(insert_fields.len()).join(", "),
-        table.id.fmt_for_select()
-    )
-}
-
-fn query_default_sql(
+/// Inserts a row, returning the id and all fields generated by the database.
+fn insert_sql(
     table: &Table<PgBackend>,
+    insert_fields: &[&TableField<PgBackend>],
     default_fields: &[&TableField<PgBackend>],
 ) -> String {
     format!(
-        "SELECT {} FROM {} WHERE {} = {}",
-        default_fields
-            .iter()
+        "INSERT INTO {} ({}) VALUES ({}) RETURNING {}",
+        table.qualified_table(),
+        insert_fields.iter().map(|field| field.column()).join(", "),
+        PgBindings::default().take(insert_fields.len()).join(", "),
+        std::iter::once(&table.id)
+            .chain(default_fields.iter().copied())
             .map(|field| field.fmt_for_select())
-            .join(", "),
-        table.table,
-        table.id.column(),
-        PgBindings::default().next().unwrap()
+            .join(", ")
     )
 }
 
@@ -43,40 +36,30 @@ pub fn impl_insert(table: &Table<PgBackend>) -> TokenStream {
 
     let id_ident = &table.id.field;
     let table_ident = &table.ident;
-    let insert_field_idents = insert_fields
-        .iter()
-        .map(|field| &field.field)
+    let insert_field_idents = Table::struct_fields(insert_fields.iter().copied())
+        .into_iter()
+        .map(|(ident, _)| ident)
         .collect::<Vec<&Ident>>();
     let default_field_idents = default_fields
         .iter()
         .map(|field| &field.field)
         .collect::<Vec<&Ident>>();
 
-    let insert_sql = insert_sql(table, &insert_fields);
-
-    let query_default_sql = query_default_sql(table, &default_fields);
-    let query_default = if default_fields.is_empty() {
-        quote!()
-    } else {
-        quote! {
-            let _generated = sqlx::query!(#query_default_sql, _id)
-                .fetch_one(db)
-                .await?;
-        }
-    };
+    let insert_sql = insert_sql(table, &insert_fields, &default_fields);
+    let default_field_values = default_fields.iter().map(|field| {
+        let ty = &field.ty;
+        record_field(quote!(_row), &field.field, &quote!(#ty))
+    });
 
     let insert_field_exprs = insert_fields
         .iter()
-        .map(|field| {
-            let ident = &field.field;
-            let ty = &field.ty;
-            match field.custom_type {
-                true => quote!(self.#ident as #ty),
-                false => quote!(self.#ident),
-            }
-        })
+        .map(|field| field.arg(quote!(self)))
         .collect::<Vec<TokenStream>>();
+    let insert = query(&insert_sql, &insert_field_exprs);
+    let id_ty = &table.id.ty;
+    let id = record_field(quote!(_row), id_ident, &quote!(#id_ty));
 
+    let skipped = table.skipped_fields();
     let box_future = crate::utils::box_future();
     quote! {
         impl ormx::Insert for #insert_ident {
@@ -85,19 +68,17 @@ pub fn impl_insert(table: &Table<PgBackend>) -> TokenStream {
             fn insert(
                 self,
                 db: &mut sqlx::PgConnection,
-            ) -> #box_future<sqlx::Result<Self::Table>> {
+            ) -> #box_future<'_, sqlx::Result<Self::Table>> {
                 Box::pin(async move {
-                    let _id = sqlx::query!(#insert_sql, #( #insert_field_exprs, )*)
+                    let _row = #insert
                         .fetch_one(db as &mut sqlx::PgConnection)
-                        .await?
-                        .#id_ident;
-
-                    #query_default
+                        .await?;
 
                     Ok(Self::Table {
-                        #id_ident: _id as _,
+                        #id_ident: #id as _,
                         #( #insert_field_idents: self.#insert_field_idents, )*
-                        #( #default_field_idents: _generated.#default_field_idents, )*
+                        #( #default_field_idents: #default_field_values, )*
+                        #skipped
                     })
                 })
             }
@@ -0,0 +1,56 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::Ident;
+
+use crate::backend::postgres::{PgBackend, PgBindings};
+use crate::table::Table;
+use crate::utils::custom_type_arg;
+
+/// Generates `by_{field}_path(path, value)` for every field annotated with `#[ormx(json)]`,
+/// returning the rows in which the value at the path (separated by `.`) equals `value`.
+pub fn path_getters(table: &Table<PgBackend>) -> TokenStream {
+    let vis = &table.vis;
+    let column_list = table.select_column_list();
+    let getters = table.fields.iter().filter(|field| field.json).map(|field| {
+        let mut bindings = PgBindings::default();
+        let sql = format!(
+            "SELECT {} FROM {} WHERE ({} #> string_to_array({}, '.'))::jsonb = {}",
+            column_list,
+            table.qualified_table(),
+            field.column(),
+            bindings.next().unwrap(),
+            bindings.next().unwrap()
+        );
+        let value = custom_type_arg(
+            quote!(sqlx::types::Json(value)),
+            &syn::parse2(quote!(sqlx::types::Json<&V>)).unwrap(),
+        );
+        let query = table.query_as_self(&sql, &[quote!(path), value]);
+        let ident = Ident::new(
+            &format!("by_{}_path", field.field.unraw()),
+            Span::call_site(),
+        );
+        quote! {
+            #vis async fn #ident<V>(
+                db: impl sqlx::Executor<'_, Database = ormx::Db>,
+                path: &str,
+                value: &V,
+            ) -> sqlx::Result<Vec<Self>>
+            where
+                V: ormx::exports::serde::Serialize + Sync,
+            {
+                #query
+                    .fetch_all(db)
+                    .await
+            }
+        }
+    });
+
+    let table_ident = &table.ident;
+    quote! {
+        impl #table_ident {
+            #( #getters )*
+        }
+    }
+}
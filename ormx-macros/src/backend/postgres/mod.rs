@@ -2,16 +2,24 @@ use std::borrow::Cow;
 
 use proc_macro2::TokenStream;
 
-use crate::backend::Backend;
+use syn::Result;
+
+use crate::attrs::Index;
+use crate::backend::{common, Backend};
 use crate::table::Table;
 
 mod insert;
+mod json;
+mod schema;
 
 #[derive(Clone)]
 pub struct PgBackend;
 
 impl Backend for PgBackend {
     const QUOTE: char = '"';
+    const AUTO_INCREMENT: &'static str = "GENERATED BY DEFAULT AS IDENTITY";
+    const IDENTITY: bool = true;
+    const RETURNING: bool = true;
     #[rustfmt::skip]
     const RESERVED_IDENTS: &'static [&'static str] = &[
         "ALL", "ANALYSE", "ANALYZE", "AND", "ANY", "ARRAY", "AS", "ASC", "ASYMMETRIC", 
@@ -27,8 +35,31 @@ impl Backend for PgBackend {
         "TO", "TRAILING", "TRUE", "UNION", "UNIQUE", "USER", "USING", "VARIADIC", "VERBOSE", "WHEN",
         "WHERE", "WINDOW", "WITH"
     ];
+    const INLINE_INDEXES: bool = false;
+    const INLINE_COMMENTS: bool = false;
+    const LOWERCASE_IDENTS: bool = true;
+    const LIST_AS_ARRAY: bool = true;
     type Bindings = PgBindings;
 
+    /// Also generates the path getters of fields annotated with `#[ormx(json)]`
+    fn impl_getters(table: &Table<Self>) -> TokenStream {
+        let mut getters = common::getters::<Self>(table);
+        getters.extend(json::path_getters(table));
+        getters
+    }
+
+    // `IS NOT DISTINCT FROM` can't use an index, while both branches of the `OR` can
+    fn null_safe_eq(column: &str, placeholder: &str) -> String {
+        format!(
+            "({0} = {1} OR ({0} IS NULL AND {1} IS NULL))",
+            column, placeholder
+        )
+    }
+
+    fn index_sql(table: &Table<Self>, index: &Index) -> Result<String> {
+        schema::index_sql(table, index)
+    }
+
     fn impl_insert(table: &Table<Self>) -> TokenStream {
         insert::impl_insert(table)
     }
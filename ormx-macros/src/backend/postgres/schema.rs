@@ -0,0 +1,47 @@
+use itertools::Itertools;
+use syn::{Error, Result};
+
+use crate::attrs::Index;
+use crate::backend::postgres::PgBackend;
+use crate::backend::Backend;
+use crate::table::Table;
+
+const INDEX_METHODS: &[&str] = &["btree", "hash", "gist", "spgist", "gin", "brin"];
+
+/// Renders `CREATE [UNIQUE] INDEX [CONCURRENTLY] IF NOT EXISTS <name> ON <table> [USING <method>]`.
+pub fn index_sql(table: &Table<PgBackend>, index: &Index) -> Result<String> {
+    if index.fulltext || index.spatial {
+        return Err(Error::new(
+            index.span,
+            "fulltext and spatial indexes are only supported on mysql, use `method = gin` or \
+             `method = gist` instead",
+        ));
+    }
+    let mut sql = String::from("CREATE ");
+    if index.unique {
+        sql.push_str("UNIQUE ");
+    }
+    sql.push_str("INDEX ");
+    if index.concurrently {
+        sql.push_str("CONCURRENTLY ");
+    }
+    sql.push_str(&format!(
+        "IF NOT EXISTS {} ON {}",
+        PgBackend::quoted(&table.index_name(index)),
+        table.qualified_table()
+    ));
+    if let Some(method) = &index.method {
+        if !INDEX_METHODS.contains(&&*method.to_string()) {
+            let message = format!("expected one of {}", INDEX_METHODS.join(", "));
+            return Err(Error::new(method.span(), message));
+        }
+        sql.push_str(&format!(" USING {}", method.to_string().to_uppercase()));
+    }
+    let columns = index
+        .columns
+        .iter()
+        .map(|column| table.field(column).column())
+        .join(", ");
+    sql.push_str(&format!(" ({})", columns));
+    Ok(sql)
+}
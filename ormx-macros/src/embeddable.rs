@@ -0,0 +1,251 @@
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::{quote, ToTokens};
+use syn::ext::IdentExt;
+use syn::{Data, DeriveInput, Error, Field, Fields, FieldsNamed, Ident, Path, Result, Type};
+
+use crate::attrs::{parse_attrs, EmbeddableFieldAttr};
+use crate::query2::splice::{self, call_hidden_macro, embeddable_macro_ident};
+use crate::utils::{option_inner, set_once};
+
+pub fn derive(input: DeriveInput) -> Result<TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input,
+                    "Embeddable can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input,
+                "Embeddable can only be derived for structs with named fields",
+            ))
+        }
+    };
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "Embeddable can't be derived for generic structs",
+        ));
+    }
+
+    let columns = fields
+        .named
+        .iter()
+        .map(|field| {
+            let (column, _) = parse_field(field)?;
+            Ok(column.unwrap_or_else(|| field.ident.as_ref().unwrap().unraw().to_string()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // the attributes are parsed again by the table the struct is embedded into
+    let fields = fields.named.iter().map(|field| {
+        let attrs = field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("ormx") || attr.path.is_ident("doc"));
+        let (ident, ty) = (&field.ident, &field.ty);
+        quote!(#( #attrs )* #ident: #ty)
+    });
+
+    let ident = &input.ident;
+    let embeddable_macro = splice::embeddable_macro(ident, quote!(#( #fields ),*));
+    Ok(quote! {
+        impl ormx::Embeddable for #ident {
+            const COLUMNS: &'static [&'static str] = &[ #( #columns ),* ];
+        }
+
+        #embeddable_macro
+    })
+}
+
+/// Parses the attributes of a field of an embeddable struct, returning the name of its column
+/// (without the prefix) if given using `#[ormx(column = ..)]` and whether it is annotated with
+/// `#[ormx(custom_type)]`.
+pub fn parse_field(field: &Field) -> Result<(Option<String>, bool)> {
+    let (mut column, mut custom_type, mut sql_type) = (None, None, None);
+    for attr in parse_attrs::<EmbeddableFieldAttr>(&field.attrs)? {
+        match attr {
+            EmbeddableFieldAttr::Column(x) => set_once(&mut column, x)?,
+            EmbeddableFieldAttr::CustomType(..) => set_once(&mut custom_type, true)?,
+            EmbeddableFieldAttr::SqlType(x) => set_once(&mut sql_type, x)?,
+        }
+    }
+    Ok((column, custom_type.unwrap_or(false)))
+}
+
+/// A column of a field annotated with `#[ormx(flatten)]`, mapped to a field of the embeddable
+/// struct.
+#[derive(Clone)]
+pub struct Embedded {
+    /// The field containing the embeddable struct.
+    pub field: Ident,
+    /// The embeddable struct.
+    pub path: Path,
+    /// Whether the field is an `Option` of the embeddable struct, so every column may be NULL.
+    pub optional: bool,
+    /// The field of the embeddable struct.
+    pub inner: Ident,
+    pub inner_ty: Type,
+}
+
+impl Embedded {
+    pub fn new(field: &Ident, ty: &Type, inner: &Field) -> Result<Self> {
+        Ok(Embedded {
+            field: field.clone(),
+            path: embeddable_path(ty)?,
+            optional: option_inner(ty).is_some(),
+            inner: inner.ident.clone().unwrap(),
+            inner_ty: inner.ty.clone(),
+        })
+    }
+
+    /// The identifier the column is selected as, e.g. `address__street`.
+    pub fn ident(&self) -> Ident {
+        Ident::new(
+            &format!("{}__{}", self.field.unraw(), self.inner.unraw()),
+            Span::call_site(),
+        )
+    }
+
+    /// The type of the column, which is an `Option` if the embeddable struct is optional.
+    pub fn column_ty(&self) -> Type {
+        let ty = &self.inner_ty;
+        match self.optional && option_inner(ty).is_none() {
+            true => syn::parse2(quote!(Option<#ty>)).unwrap(),
+            false => ty.clone(),
+        }
+    }
+
+    /// Returns the value of the column within `base`, e.g. `self.address.street`.
+    /// If the embeddable struct is optional, the value is an `Option` referencing the field.
+    pub fn value(&self, base: TokenStream) -> TokenStream {
+        let (field, inner) = (&self.field, &self.inner);
+        match (self.optional, option_inner(&self.inner_ty).is_some()) {
+            (false, _) => quote!(#base.#field.#inner),
+            (true, false) => quote!(#base.#field.as_ref().map(|e| &e.#inner)),
+            (true, true) => quote!(#base.#field.as_ref().and_then(|e| e.#inner.as_ref())),
+        }
+    }
+
+    /// The type of [Embedded::value], used to pass it as an argument of a custom type.
+    pub fn value_ty(&self) -> Type {
+        let ty = option_inner(&self.inner_ty).unwrap_or(&self.inner_ty);
+        match self.optional {
+            true => syn::parse2(quote!(Option<&#ty>)).unwrap(),
+            false => self.inner_ty.clone(),
+        }
+    }
+}
+
+/// Constructs the embeddable struct from the values of its columns.
+/// An optional struct is `None` if the column of a field which is not an `Option` is NULL, or if
+/// every column is NULL.
+pub fn construct(columns: &[(&Embedded, TokenStream)]) -> TokenStream {
+    let first = columns[0].0;
+    let path = &first.path;
+    let inner = columns.iter().map(|(embedded, _)| &embedded.inner);
+    if !first.optional {
+        let values = columns.iter().map(|(_, value)| value);
+        return quote!(#path { #( #inner: #values, )* });
+    }
+
+    let values = columns.iter().map(|(_, value)| value);
+    let patterns = columns.iter().map(|(embedded, _)| {
+        let inner = &embedded.inner;
+        match option_inner(&embedded.inner_ty) {
+            Some(_) => quote!(#inner),
+            None => quote!(Some(#inner)),
+        }
+    });
+    let all_optional = columns
+        .iter()
+        .all(|(embedded, _)| option_inner(&embedded.inner_ty).is_some());
+    let guard = match all_optional {
+        true => {
+            let inner = inner.clone();
+            quote!(if #( #inner.is_some() )||*)
+        }
+        false => quote!(),
+    };
+    quote! {
+        match ( #( #values, )* ) {
+            ( #( #patterns, )* ) #guard => Some(#path { #( #inner, )* }),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the path of the embeddable struct, given the type of a flattened field.
+fn embeddable_path(ty: &Type) -> Result<Path> {
+    match option_inner(ty).unwrap_or(ty) {
+        Type::Path(path) if path.qself.is_none() => Ok(path.path.clone()),
+        ty => Err(Error::new_spanned(
+            ty,
+            "expected a struct deriving Embeddable, or an Option of it",
+        )),
+    }
+}
+
+pub enum Resolved {
+    /// The fields of the embeddable structs, in the order of the flattened fields
+    Done(Vec<FieldsNamed>, Box<DeriveInput>),
+    /// The hidden macro of an embeddable struct has to be called
+    Pending(TokenStream),
+}
+
+/// Resolves the fields of the embeddable structs of the fields annotated with `#[ormx(flatten)]`
+/// by calling their hidden macros, which will call `callback` again, one after another.
+pub fn resolve(input: TokenStream, callback: TokenStream) -> Result<Resolved> {
+    let (resolved, input) = splice::split_resolved(input);
+    let parsed = syn::parse2::<DeriveInput>(input.clone())?;
+    match flattened_types(&parsed).get(resolved.len()) {
+        Some(ty) => {
+            let path = embeddable_path(ty)?;
+            let call = call_hidden_macro(path, embeddable_macro_ident, &resolved, callback, input)?;
+            Ok(Resolved::Pending(call))
+        }
+        None => {
+            let fields = resolved
+                .iter()
+                .map(|data| syn::parse2::<FieldsNamed>(data.to_token_stream()))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Resolved::Done(fields, Box::new(parsed)))
+        }
+    }
+}
+
+/// Returns the types of the fields annotated with `#[ormx(flatten)]`.
+/// The attributes are parsed later on, so this only looks for the `flatten` keyword.
+fn flattened_types(input: &DeriveInput) -> Vec<&Type> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => return vec![],
+    };
+    let is_flattened = |field: &&Field| {
+        field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("ormx"))
+            .any(|attr| {
+                let args = match attr.tokens.clone().into_iter().next() {
+                    Some(TokenTree::Group(group)) => group.stream(),
+                    _ => return false,
+                };
+                let mut first = true;
+                args.into_iter().any(|token| {
+                    let flatten = first && matches!(&token, TokenTree::Ident(i) if i == "flatten");
+                    first = matches!(&token, TokenTree::Punct(p) if p.as_char() == ',');
+                    flatten
+                })
+            })
+    };
+    fields
+        .iter()
+        .filter(is_flattened)
+        .map(|field| &field.ty)
+        .collect()
+}
@@ -2,7 +2,10 @@
 
 mod attrs;
 mod backend;
+mod embeddable;
+mod order_by;
 mod patch;
+mod query2;
 mod table;
 mod utils;
 
@@ -34,11 +37,28 @@ mod utils;
 /// # CRUD
 /// See the documentation of [Table](trait.Table.html)
 ///
+/// # Table names
+/// Tables in a schema other than the current one are declared using `#[ormx(schema = "..")]`,
+/// e.g. `#[ormx(schema = "auth", table = "user")]`. Alternatively, the table may be given as
+/// `table = "auth.user"`, which is split at the first dot outside of quotes. Names which are
+/// reserved keywords are quoted, so the table above becomes `auth."user"` on postgres. Other names
+/// are used as they are, so on postgres, names which have to keep their case have to be quoted,
+/// e.g. `table = "\"Users\""`.
+///
+/// # Column names
+/// By default, the column of a field is named like the field. Columns following a different naming
+/// convention can be named using `#[ormx(rename_all = "..")]` on the struct, which accepts
+/// `"camelCase"`, `"PascalCase"` and `"SCREAMING_SNAKE_CASE"`. `#[ormx(column = "..")]` on a field
+/// takes precedence. The columns of flattened fields are renamed including their prefix.
+/// Columns which are reserved keywords are quoted. On postgres, columns named using `rename_all`
+/// are quoted as well if they contain uppercase characters, so they keep their case.
+///
 /// # Insertable
 /// ormx will generate a helper struct for inserting rows into the database when using
 /// `#[ormx(insertable)]`.  This struct will contain all fields of the struct, except
 /// - the ID
 /// - fields annotated with `#[ormx(default)]`
+/// - fields annotated with `#[ormx(identity = always)]` or `#[ormx(read_only)]`
 ///
 /// since the value of these fields will be generated by the database.
 /// By default, this struct will be named `Insert{struct_name}`, though this can be changed by
@@ -61,11 +81,24 @@ mod utils;
 /// `#[ormx(get_many)]`:
 /// `{pub} async fn get_by_{field_name}(&{field_type}) -> Result<Vec<Self>>`
 ///
+/// `#[ormx(get_range)]`:
+/// `{pub} async fn by_{field_name}_between(&{field_type}, &{field_type}) -> Result<Vec<Self>>`
+///
 /// By default, the function will be named `get_by_{field_name)`, though this can be changed by
 /// supplying a custom name: `#[ormx(get_one = by_id)]`.
 /// By default, the function will take a reference to the type of the annotated field as an argument,
 /// though this can be changed by supplying a custom type: `#[ormx(get_one(&str)]`.
 ///
+/// For fields of type `Option<T>`, the generated query compares the column using
+/// `(col = $1 OR (col IS NULL AND $1 IS NULL))` on postgres and `<=>` on mysql instead of `=`, so
+/// passing `None` will match rows in which the column is NULL, while an index of the column can
+/// still be used.
+///
+/// The rows returned by a getter can be ordered by supplying `order_by`:
+/// `#[ormx(get_many(order_by = "created_at DESC"))]`.
+/// `#[ormx(get_many)]` and `#[ormx(get_range)]` also accept `limit`, which adds an additional
+/// `limit: i64` argument to the generated function: `#[ormx(get_many(&str, limit))]`.
+///
 /// # Accessors: Setters
 /// ormx will generate accessor functions for fields annotated with `#[ormx(set)]`.
 /// These functions can be used to update a single field of an entity.
@@ -76,28 +109,430 @@ mod utils;
 /// By default, the function will be named `set_{field_name)`, though this can be changed by
 /// supplying a custom name: `#[ormx(set = set_name)]`.
 ///
+/// # Read-only fields
+/// Fields annotated with `#[ormx(read_only)]` are selected, but never written, which is useful for
+/// computed columns (`GENERATED ALWAYS AS (..) STORED`) or columns maintained by triggers.
+/// They are excluded from inserts, `Table::update` and can't have setters.
+/// After inserting a row, their values are read back from the database, using `RETURNING` on
+/// postgres. `Table::update` does not modify the row, so use `Table::update_and_refresh` to refresh
+/// them after updating a row. On mysql, refreshing them requires a second query, so the setters of
+/// tables with read-only fields take a connection which can be acquired (`impl sqlx::Acquire`),
+/// like `Table::update_and_refresh`.
+///
+/// # Skipped fields
+/// Fields annotated with `#[ormx(skip)]` don't correspond to a column, and are excluded from every
+/// generated query, the insertable struct, the metadata and the schema. When a row is loaded, they
+/// are initialized using `Default::default()`, or using an expression:
+/// `#[ormx(skip(default = Vec::new()))]`.
+/// `conditional_query_as!` can only return tables with skipped fields if their columns are spliced
+/// using `@columns(..)`, or if the `runtime-queries` feature is enabled.
+///
+/// # Flattened fields
+/// Fields annotated with `#[ormx(flatten)]` are mapped to the columns of a struct deriving
+/// [Embeddable](derive.Embeddable.html), optionally prefixed using `#[ormx(prefix = "..")]`:
+/// ```rust,ignore
+/// #[derive(ormx::Embeddable)]
+/// struct Address {
+///     street: String,
+///     city: String,
+///     zip: Option<String>,
+/// }
+///
+/// #[derive(ormx::Table)]
+/// #[ormx(table = "users", id = id, insertable)]
+/// struct User {
+///     id: i32,
+///     // columns `address_street`, `address_city` and `address_zip`
+///     #[ormx(flatten, prefix = "address_")]
+///     address: Address,
+/// }
+/// ```
+/// The columns are part of every query, the insertable struct and `Table::update`.
+/// If the field is an `Option`, all of its columns may be NULL. The field is then `None` if the
+/// column of a field which is not an `Option` is NULL, or if every column is NULL.
+/// The embeddable struct is resolved using a hidden macro next to it, so it must either be
+/// declared in the same module as the table, or be referred to by its path, e.g.
+/// `address: crate::address::Address`.
+/// Like skipped fields, flattened fields can only be returned by `conditional_query_as!` if the
+/// columns are spliced using `@columns(..)`, or if the `runtime-queries` feature is enabled.
+///
+/// # JSON fields
+/// Fields annotated with `#[ormx(json)]` are stored as JSON, wrapping them in `sqlx::types::Json`.
+/// Their type may be any `T: Serialize + DeserializeOwned`, or an `Option` of it. This requires the
+/// `json` feature.
+/// JSON fields can't be combined with `custom_type`, `default`, `read_only` or getters.
+/// On postgres, a getter querying rows by a value within the JSON document is generated:
+/// `{pub} async fn by_{field_name}_path(path: &str, value: &V) -> Result<Vec<Self>>`.
+/// The path is separated by `.`, e.g. `by_settings_path(db, "font.size", &12)`.
+/// Like skipped fields, JSON fields can only be returned by `conditional_query_as!` if the
+/// columns are spliced using `@columns(..)`, or if the `runtime-queries` feature is enabled.
+///
+/// # Metadata
+/// ormx implements [TableMeta](trait.TableMeta.html), exposing the name of the table, the ID column
+/// and information about every column at runtime.
+/// Also, a typed marker is generated for every field, named after the field in uppercase:
+/// `{pub} const {FIELD_NAME}: Column<Self, {field_type}>`
+///
+/// The column list used by the generated queries is available as
+/// [TableMeta::SELECT_COLUMNS](trait.TableMeta.html#associatedconstant.SELECT_COLUMNS), and can be
+/// spliced into [conditional_query_as!](macro.conditional_query_as.html) using `@columns(Table)`.
+///
 /// # Custom types
 /// When using custom types (which implement `sqlx::Type`), the field has to annotated with
 /// `#[ormx(custom_type)]`. This will use a column type override for querying this field
 /// (see [the sqlx docs on this](https://docs.rs/sqlx/0.4.0-beta.1/sqlx/macro.query_as.html#column-type-override-infer-from-struct-field)).
 ///
+/// # Schema
+/// For tables annotated with `#[ormx(ddl)]`, ormx implements [Schema](trait.Schema.html), which
+/// generates the `CREATE TABLE IF NOT EXISTS` statement of the table:
+/// `{Table}::create_table_sql() -> String` and `{Table}::create_table(db) -> Result<()>`.
+///
+/// The columns are described using these attributes:
+/// - `#[ormx(sql_type = "VARCHAR(128)")]`: the type of the column. If omitted, it is inferred from
+///   the type of the field using [SqlType](trait.SqlType.html), which is implemented for common
+///   types and can be implemented for custom types.
+///   Columns are `NOT NULL` unless the field is an `Option`.
+/// - `#[ormx(primary_key)]`: the primary key of the table, which may span multiple fields.
+///   Defaults to the ID.
+/// - `#[ormx(auto_increment)]`: the values of the column are generated by the database, using
+///   `AUTO_INCREMENT` on mysql and `GENERATED BY DEFAULT AS IDENTITY` on postgres.
+/// - `#[ormx(identity = by_default)]` or `#[ormx(identity = always)]`: an identity column on
+///   postgres, `GENERATED BY DEFAULT AS IDENTITY` or `GENERATED ALWAYS AS IDENTITY`.
+///   Since the database rejects writing to a column generated always, it is never inserted or
+///   updated, not even by `Table::update`, and can't have a setter. Its value is read back after
+///   inserting, like the value of a column annotated with `#[ormx(default)]`. Patches must not
+///   contain it either.
+/// - `#[ormx(default = "now()")]`: the default value of the column. Like `#[ormx(default)]`, the
+///   field is not part of the insertable struct.
+/// - `#[ormx(unique)]`: creates a unique index on the column.
+/// - `#[ormx(references = "organizations(id)", on_delete = cascade, on_update = restrict)]`: a
+///   foreign key. The actions are `cascade`, `restrict`, `set_null`, `set_default` and
+///   `no_action`, which is the default. Foreign keys are also available through
+///   [ColumnMeta::references](struct.ColumnMeta.html#structfield.references), even without
+///   `#[ormx(ddl)]`.
+///
+/// Doc comments on the struct and its fields become comments on the table and its columns, which
+/// are set by `COMMENT ON` statements on postgres and within `CREATE TABLE` on mysql.
+///
+/// Indexes spanning multiple columns are declared on the struct:
+/// `#[ormx(index(columns = (last_name, first_name), name = "users_name_idx", method = btree))]`.
+/// `name` defaults to `{table}_{columns}_idx`, or `{table}_{columns}_key` for `unique` indexes.
+/// Index names are always quoted, so `name` is used exactly as given.
+/// On postgres, `method` is one of `btree`, `hash`, `gist`, `spgist`, `gin` and `brin`, and
+/// indexes are created using `CREATE INDEX IF NOT EXISTS`, which may be `concurrently`.
+/// On mysql, `method` is either `btree` or `hash`, indexes may also be `fulltext` or `spatial`, and
+/// are created within `CREATE TABLE`.
+///
+/// To check that a table in the database matches the struct, e.g. at startup, use
+/// [verify_schema](fn.verify_schema.html), which works with or without `#[ormx(ddl)]`.
+///
+/// To bring an existing table up to date, [alter_sync](fn.alter_sync.html) compares the struct
+/// with the database and returns a [Migration](struct.Migration.html), which can be written into
+/// `migrations/` as reversible migration for `sqlx migrate`. To rename a column instead of
+/// dropping and adding it, annotate the field with `#[ormx(renamed_from = "<old column>")]`.
+/// [force_sync](fn.force_sync.html) drops and recreates the table instead.
+///
+/// # Runtime queries
+/// With the `runtime-queries` feature, the generated queries use `sqlx::query()` and `.bind()`
+/// instead of `sqlx::query_as!`, so no database is required at compile time. The queries are then
+/// only checked when they are executed. The rows are read by the names of their columns using a
+/// hidden function, so the table doesn't have to implement `sqlx::FromRow`, but may derive it.
+///
 #[proc_macro_error::proc_macro_error]
 #[proc_macro_derive(Table, attributes(ormx))]
 pub fn derive_table(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let input = syn::parse_macro_input!(input as syn::DeriveInput);
-    match table::derive(input) {
+    match table::derive(input.into()) {
         Ok(ok) => ok,
         Err(err) => err.to_compile_error(),
     }
     .into()
 }
 
+/// Called by the hidden macros of embeddable structs once their fields have been resolved.
+#[doc(hidden)]
+#[proc_macro_error::proc_macro_error]
+#[proc_macro]
+pub fn __derive_table(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    derive_table(input)
+}
+
 /// Derives [Patch](trait.Patch.html).
+///
+/// Like in tables, fields annotated with `#[ormx(flatten)]` are mapped to the columns of an
+/// embeddable struct, see [derive(Table)](derive.Table.html).
+/// Fields annotated with `#[ormx(json)]` are stored as JSON.
+/// Like in tables, the columns can be renamed using `#[ormx(rename_all = "..")]` and the table can
+/// be qualified using `#[ormx(schema = "..")]`.
 #[proc_macro_error::proc_macro_error]
 #[proc_macro_derive(Patch, attributes(ormx))]
 pub fn derive_patch(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    match patch::derive(input.into()) {
+        Ok(ok) => ok,
+        Err(err) => err.to_compile_error(),
+    }
+    .into()
+}
+
+/// Called by the hidden macros of embeddable structs once their fields have been resolved.
+#[doc(hidden)]
+#[proc_macro_error::proc_macro_error]
+#[proc_macro]
+pub fn __derive_patch(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    derive_patch(input)
+}
+
+/// Derives [Embeddable](trait.Embeddable.html) for a struct, so that it can be flattened into
+/// tables and patches using `#[ormx(flatten)]`.
+///
+/// Its fields may be annotated with `#[ormx(column = "..")]`, `#[ormx(custom_type)]` and
+/// `#[ormx(sql_type = "..")]`. The prefix of the flattened field is prepended to every column.
+#[proc_macro_error::proc_macro_error]
+#[proc_macro_derive(Embeddable, attributes(ormx))]
+pub fn derive_embeddable(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    match embeddable::derive(input) {
+        Ok(ok) => ok,
+        Err(err) => err.to_compile_error(),
+    }
+    .into()
+}
+
+/// Derives [OrderBy](trait.OrderBy.html) for an enum, so that it can be used to choose the order of
+/// the rows returned by a conditional query.
+///
+/// Every variant is mapped to the SQL following `ORDER BY` using `#[ormx(order_by = "..")]`:
+/// ```rust,ignore
+/// #[derive(ormx::OrderBy)]
+/// enum UserSort {
+///     #[ormx(order_by = "first_name ASC")]
+///     FirstName,
+///     #[ormx(order_by = "last_login DESC NULLS LAST, id")]
+///     LastLogin,
+/// }
+/// ```
+/// See the `# Ordering` section of [conditional_query_as!](macro.conditional_query_as.html).
+#[proc_macro_error::proc_macro_error]
+#[proc_macro_derive(OrderBy, attributes(ormx))]
+pub fn derive_order_by(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = syn::parse_macro_input!(input as syn::DeriveInput);
-    match patch::derive(input) {
+    match order_by::derive(input) {
+        Ok(ok) => ok,
+        Err(err) => err.to_compile_error(),
+    }
+    .into()
+}
+
+/// An improved version of `sqlx::query_as!`.
+///
+/// Returns a [ConditionalQuery](struct.ConditionalQuery.html), which can be executed using
+/// `fetch`, `fetch_all`, `fetch_one` and `fetch_optional`.
+///
+/// Rows are mapped to `PATH` like by `sqlx::query_as!`, using the query in which every condition
+/// takes its default branch (see `# Conditions`). If `PATH` is a table whose columns are spliced
+/// into the query using `@columns(PATH)`, rows are mapped like by the queries generated by
+/// [Table](derive.Table.html) instead.
+///
+/// With the `runtime-queries` feature, the query is not checked at compile time, and `PATH` has to
+/// implement `sqlx::FromRow`, unless it is a table whose columns are spliced.
+///
+/// # Syntax
+/// The syntax of `conditional_query_as!` differs from the original `sqlx::query_as!`.
+/// Formally, it accepts this syntax:
+/// ```rust,ignore
+/// conditional_query_as!(
+///     PATH,
+///     (
+///         LITERAL |
+///         ?(EXPRESSION) |
+///         ?*(EXPRESSION) |
+///         @columns(PATH) |
+///         order_by!(EXPRESSION: PATH) |
+///         CONDITION
+///     )*
+/// )
+/// ```
+/// Arguments are now provided inline with the query like this:
+/// ```rust,ignore
+/// let user_id = Some(2);
+/// conditional_query_as!(
+///     User,
+///     "SELECT * FROM users WHERE user_id =" ?(user_id.unwrap())
+/// );
+/// ```
+/// Also, `conditional_query_as!` can parse multiple string literals like this:
+/// ```rust,ignore
+/// conditional_query_as!(
+///     User,
+///     "SELECT * FROM users"
+///     "WHERE user_id =" ?(user_id)
+///     "AND first_name =" ?(first_name)
+/// );
+/// ```
+///
+/// # Lists
+/// A list of values can be compared against using `?*(EXPRESSION)`, where `EXPRESSION` is anything
+/// implementing `IntoIterator`. On postgres, this expands to `= ANY($n)`, binding the elements as an
+/// array. On mysql, this expands to `IN (?, ..)`, with one placeholder for every element:
+/// ```rust,ignore
+/// let ids = vec![1, 2, 3];
+/// conditional_query_as!(
+///     User,
+///     "SELECT * FROM users WHERE user_id" ?*(ids)
+/// );
+/// ```
+/// The query is checked with the same comparison, binding an array on postgres and a single
+/// element on mysql, so the type of the elements is checked.
+///
+/// # Limits
+/// A single statement can bind at most 65535 parameters. Executing a query binding more parameters
+/// fails with `sqlx::Error::Protocol` before it is sent to the database. Since every element of a
+/// list binds its own parameter on mysql, this can happen when binding large lists.
+///
+/// # Columns
+/// The list of columns of a table deriving [Table](trait.Table.html) can be inserted into the
+/// query using `@columns(PATH)`, so it doesn't have to be kept in sync by hand:
+/// ```rust,ignore
+/// conditional_query_as!(
+///     User,
+///     "SELECT" @columns(crate::User) "FROM users"
+/// );
+/// ```
+/// Since this is implemented by a macro generated next to the table, `PATH` has to point to the
+/// table within the module it is defined in (e.g `crate::User`) and not to a re-export of it.
+///
+/// # Ordering
+/// Rows can be ordered by a value chosen at runtime using `order_by!(EXPRESSION: PATH)`, where
+/// `PATH` points to an enum deriving [OrderBy](derive.OrderBy.html). This expands to a `match`
+/// choosing between the `ORDER BY` clauses of all variants, so every one of them is checked and
+/// the SQL never contains user input:
+/// ```rust,ignore
+/// conditional_query_as!(
+///     User,
+///     "SELECT * FROM users"
+///     order_by!(sort: crate::UserSort)
+/// );
+/// ```
+/// Like with `@columns(PATH)`, `PATH` has to point to the enum within the module it is defined in.
+///
+/// # Conditions
+/// `conditional_query_as!` can be used to have queries depend on a condition during runtime.
+/// The SQL of the query is assembled at runtime, while the query is checked at compile time.
+/// A condition is either an `if let` with an optional `else` branch, or a `match`:
+/// ```ignore
+/// PATTERN = EXPRESSION => { FRAGMENT* } (else { FRAGMENT* })?
+///
+/// match EXPRESSION {
+///     (PATTERN => { FRAGMENT* }),*
+/// }
+/// ```
+/// where `FRAGMENT` is a `LITERAL`, `?(EXPRESSION)`, `?*(EXPRESSION)` or another condition, so conditions can be
+/// nested.
+///
+/// Instead of checking every possible combination of conditions, the query is checked once with
+/// every condition taking its default branch (no branch for `PATTERN = EXPRESSION => { .. }`,
+/// the `else` branch if present, and the first arm of a `match`), once for every other branch, and
+/// once for every pair of other branches of different conditions, with all other conditions taking
+/// their default branch. The number of checked queries therefore only grows quadratically with the
+/// number of conditions, while fragments depending on each other (e.g. a `WHERE` and an `AND`) are
+/// still checked together.
+///
+/// Since the rows are mapped using the query in which every condition takes its default branch,
+/// conditions can't be placed within the list of returned columns (between `SELECT` and `FROM`, or
+/// after `RETURNING`), and can't contain another one (e.g. `UNION SELECT ..`). This does not apply
+/// to tables whose columns are spliced using `@columns(PATH)`, which are read by the names of their
+/// columns, so every branch only has to return the columns of the table, in any order.
+///
+/// Example:
+/// ```rust,ignore
+/// let limit = Some(10);
+/// conditional_query_as!(
+///     User,
+///     "SELECT * FROM users"
+///     Some(name) = filter => {
+///         "WHERE first_name =" ?(name)
+///         Some(email) = email => {
+///             "AND email =" ?(email)
+///         }
+///     }
+///     match order {
+///         Order::Name => { "ORDER BY first_name" },
+///         Order::Newest => { "ORDER BY user_id DESC" },
+///     }
+///     Some(l) = limit => {
+///         "LIMIT" ?(l)
+///     } else {
+///         "LIMIT 100"
+///     }
+/// );
+/// ```
+///
+#[proc_macro]
+pub fn conditional_query_as(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    conditional_query_impl(input, query2::Macro::QueryAs)
+}
+
+/// Like [conditional_query_as!](macro.conditional_query_as.html), but maps rows to an anonymous
+/// record like `sqlx::query!`.
+///
+/// ```rust,ignore
+/// let users = conditional_query!(
+///     "SELECT id, first_name FROM users"
+///     Some(name) = filter => { "WHERE first_name =" ?(name) }
+/// )
+/// .fetch_all(&db)
+/// .await?;
+/// println!("{}", users[0].first_name);
+/// ```
+///
+/// The fields of the record are taken from the query in which every condition takes its default
+/// branch, and are read by their position. Therefore, conditions can't be placed within the list
+/// of returned columns (between `SELECT` and `FROM`, or after `RETURNING`), and can't contain
+/// another one (e.g. `UNION SELECT ..`).
+///
+/// Since the fields of the record are taken from the database, this macro is not available with
+/// the `runtime-queries` feature.
+#[proc_macro]
+pub fn conditional_query(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    conditional_query_impl(input, query2::Macro::Query)
+}
+
+/// Like [conditional_query_as!](macro.conditional_query_as.html), but returns the value of the
+/// single column of every row like `sqlx::query_scalar!`.
+///
+/// ```rust,ignore
+/// let count = conditional_query_scalar!(
+///     "SELECT COUNT(*) FROM users"
+///     Some(name) = filter => { "WHERE first_name =" ?(name) }
+/// )
+/// .fetch_one(&db)
+/// .await?;
+/// ```
+#[proc_macro]
+pub fn conditional_query_scalar(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    conditional_query_impl(input, query2::Macro::QueryScalar)
+}
+
+/// Like [conditional_query_as!](macro.conditional_query_as.html), but for statements which don't
+/// return rows. The resulting query can be run using `execute`.
+///
+/// ```rust,ignore
+/// conditional_execute!(
+///     "DELETE FROM users WHERE disabled IS NOT NULL"
+///     Some(before) = before => { "AND last_login <" ?(before) }
+/// )
+/// .execute(&db)
+/// .await?;
+/// ```
+#[proc_macro]
+pub fn conditional_execute(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    conditional_query_impl(input, query2::Macro::Execute)
+}
+
+fn conditional_query_impl(
+    input: proc_macro::TokenStream,
+    kind: query2::Macro,
+) -> proc_macro::TokenStream {
+    match query2::expand(input.into(), kind) {
         Ok(ok) => ok,
         Err(err) => err.to_compile_error(),
     }
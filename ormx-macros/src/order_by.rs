@@ -0,0 +1,48 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Fields, Result};
+
+use crate::attrs::{parse_attrs, OrderByVariantAttr};
+use crate::utils::{missing_attr, set_once};
+
+pub fn derive(input: DeriveInput) -> Result<TokenStream> {
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => {
+            return Err(Error::new_spanned(
+                &input,
+                "OrderBy can only be derived for enums",
+            ))
+        }
+    };
+
+    let mut variants = vec![];
+    for variant in data.variants.iter() {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new_spanned(variant, "expected a unit variant"));
+        }
+        let mut order_by = None;
+        for attr in parse_attrs::<OrderByVariantAttr>(&variant.attrs)? {
+            match attr {
+                OrderByVariantAttr::OrderBy(x) => set_once(&mut order_by, x)?,
+            }
+        }
+        let order_by = order_by.ok_or_else(|| missing_attr("order_by"))?;
+        variants.push((variant.ident.clone(), order_by));
+    }
+
+    let ident = &input.ident;
+    let (variant_idents, order_by): (Vec<_>, Vec<_>) = variants.iter().cloned().unzip();
+    let order_by_macro = crate::query2::splice::order_by_macro(ident, &variants);
+    Ok(quote! {
+        impl ormx::OrderBy for #ident {
+            fn order_by(&self) -> &'static str {
+                match self {
+                    #( Self::#variant_idents => #order_by, )*
+                }
+            }
+        }
+
+        #order_by_macro
+    })
+}
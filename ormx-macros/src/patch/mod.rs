@@ -1,27 +1,87 @@
 use std::convert::TryFrom;
 
+use itertools::Itertools;
 use proc_macro2::TokenStream;
-use syn::{DeriveInput, Ident, Path, Result, Type};
+use quote::quote;
+use syn::{Ident, Path, Result, Type};
 
+use crate::attrs::RenameAll;
 use crate::backend::{Backend, Implementation};
+use crate::embeddable::{self, Embedded, Resolved};
+use crate::utils::{custom_type_arg, json_arg};
 
 mod parse;
 
 pub struct Patch {
     pub ident: Ident,
     pub table_name: String,
+    /// The schema containing the table, declared using `#[ormx(schema = ..)]`.
+    pub schema: Option<String>,
     pub table: Path,
     pub id: String,
     pub fields: Vec<PatchField>,
+    pub rename_all: Option<RenameAll>,
 }
 
 pub struct PatchField {
     pub ident: Ident,
     pub column: String,
+    /// Whether the column is named using `#[ormx(column = ..)]`, which takes precedence over
+    /// `#[ormx(rename_all = ..)]`.
+    pub explicit_column: bool,
+    /// Whether the column is named using `#[ormx(rename_all = ..)]`.
+    pub renamed: bool,
     pub ty: Type,
+    /// Whether the field of an embeddable struct is annotated with `#[ormx(custom_type)]`.
+    pub custom_type: bool,
+    /// Whether the field is annotated with `#[ormx(json)]`.
+    pub json: bool,
+    /// The prefix of the columns of a field annotated with `#[ormx(flatten)]`, which is replaced
+    /// by its columns once the embeddable struct has been resolved.
+    pub flatten: Option<String>,
+    pub embedded: Option<Embedded>,
 }
 
-pub fn derive(input: DeriveInput) -> Result<TokenStream> {
-    let parsed = Patch::try_from(&input)?;
+impl Patch {
+    /// Returns the fields of the struct, which are only returned once if they are flattened.
+    pub fn struct_fields(&self) -> Vec<&Ident> {
+        self.fields
+            .iter()
+            .map(|field| match &field.embedded {
+                Some(embedded) => &embedded.field,
+                None => &field.ident,
+            })
+            .dedup()
+            .collect()
+    }
+}
+
+impl PatchField {
+    /// Returns the value of this column within `base`, passed as an argument of a query.
+    pub fn arg(&self, base: TokenStream) -> TokenStream {
+        match &self.embedded {
+            Some(embedded) if self.custom_type => {
+                custom_type_arg(embedded.value(base), &embedded.value_ty())
+            }
+            Some(embedded) => embedded.value(base),
+            None if self.json => {
+                let ident = &self.ident;
+                json_arg(quote!(#base.#ident), &self.ty)
+            }
+            None => {
+                let ident = &self.ident;
+                quote!(#base.#ident)
+            }
+        }
+    }
+}
+
+pub fn derive(input: TokenStream) -> Result<TokenStream> {
+    let (embeddables, input) = match embeddable::resolve(input, quote!(ormx::__derive_patch))? {
+        Resolved::Done(embeddables, input) => (embeddables, *input),
+        Resolved::Pending(call) => return Ok(call),
+    };
+    let mut parsed = Patch::try_from(&input)?;
+    parsed.flatten(embeddables)?;
     Ok(Implementation::impl_patch(&parsed))
 }
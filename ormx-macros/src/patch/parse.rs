@@ -1,10 +1,12 @@
 use std::convert::TryFrom;
 
-use syn::{Data, DeriveInput, Error, Field, Result};
+use syn::ext::IdentExt;
+use syn::{Data, DeriveInput, Error, Field, FieldsNamed, Result};
 
 use crate::attrs::{parse_attrs, PatchAttr, PatchFieldAttr};
+use crate::embeddable::{self, Embedded};
 use crate::patch::PatchField;
-use crate::utils::{missing_attr, set_once};
+use crate::utils::{missing_attr, set_once, split_schema};
 
 use super::Patch;
 
@@ -17,7 +19,7 @@ impl TryFrom<&syn::DeriveInput> for Patch {
             _ => panic!("not a struct with named fields"),
         };
 
-        let fields = data
+        let mut fields = data
             .fields
             .iter()
             .map(PatchField::try_from)
@@ -25,21 +27,37 @@ impl TryFrom<&syn::DeriveInput> for Patch {
 
         let mut table = None;
         let mut table_name = None;
+        let mut schema = None;
         let mut id = None;
+        let mut rename_all = None;
         for attr in parse_attrs::<PatchAttr>(&value.attrs)? {
             match attr {
                 PatchAttr::Table(x) => set_once(&mut table, x)?,
                 PatchAttr::TableName(x) => set_once(&mut table_name, x)?,
+                PatchAttr::Schema(x) => set_once(&mut schema, x)?,
                 PatchAttr::Id(x) => set_once(&mut id, x)?,
+                PatchAttr::RenameAll(x) => set_once(&mut rename_all, x)?,
             }
         }
 
+        if let Some(rename_all) = rename_all {
+            for field in fields.iter_mut().filter(|field| !field.explicit_column) {
+                field.column = rename_all.apply(&field.column);
+                field.renamed = true;
+            }
+        }
+
+        let table_name = table_name.ok_or_else(|| missing_attr("table_name"))?;
+        let (schema, table_name) = split_schema(schema, table_name);
+
         Ok(Patch {
             ident: value.ident.clone(),
-            table_name: table_name.ok_or_else(|| missing_attr("table_name"))?,
+            table_name,
+            schema,
             table: table.ok_or_else(|| missing_attr("table"))?,
             id: id.ok_or_else(|| missing_attr("id"))?,
             fields,
+            rename_all,
         })
     }
 }
@@ -50,17 +68,89 @@ impl TryFrom<&syn::Field> for PatchField {
     fn try_from(value: &Field) -> Result<Self> {
         let ident = value.ident.clone().unwrap();
 
-        let mut column = None;
+        let (mut column, mut json, mut flatten, mut prefix) = (None, None, None, None);
         for attr in parse_attrs::<PatchFieldAttr>(&value.attrs)? {
             match attr {
                 PatchFieldAttr::Column(x) => set_once(&mut column, x)?,
+                PatchFieldAttr::Json(..) => set_once(&mut json, true)?,
+                PatchFieldAttr::Flatten(..) => set_once(&mut flatten, ())?,
+                PatchFieldAttr::Prefix(x) => set_once(&mut prefix, x)?,
             }
         }
 
+        match flatten {
+            Some(_) if column.is_some() || json.is_some() => {
+                return Err(Error::new(
+                    ident.span(),
+                    "#[ormx(flatten)] can only be combined with #[ormx(prefix = ..)]",
+                ))
+            }
+            None if prefix.is_some() => {
+                return Err(Error::new(
+                    ident.span(),
+                    "#[ormx(prefix = ..)] requires #[ormx(flatten)]",
+                ))
+            }
+            _ => {}
+        }
+
         Ok(PatchField {
             ident: value.ident.clone().unwrap(),
-            column: column.unwrap_or_else(|| ident.to_string()),
+            explicit_column: column.is_some(),
+            renamed: false,
+            column: column.unwrap_or_else(|| ident.unraw().to_string()),
             ty: value.ty.clone(),
+            custom_type: false,
+            json: json.unwrap_or(false),
+            flatten: flatten.map(|_| prefix.unwrap_or_default()),
+            embedded: None,
         })
     }
 }
+
+impl Patch {
+    /// Replaces the fields annotated with `#[ormx(flatten)]` by the columns of their embeddable
+    /// structs, given the fields of the structs in the order of the flattened fields.
+    pub(super) fn flatten(&mut self, embeddables: Vec<FieldsNamed>) -> Result<()> {
+        let mut embeddables = embeddables.into_iter();
+        let mut fields = vec![];
+        for field in std::mem::take(&mut self.fields) {
+            let prefix = match &field.flatten {
+                Some(prefix) => prefix,
+                None => {
+                    fields.push(field);
+                    continue;
+                }
+            };
+            let embeddable = embeddables
+                .next()
+                .expect("embeddable struct has not been resolved");
+            for inner in embeddable.named.iter() {
+                let (column, custom_type) = embeddable::parse_field(inner)?;
+                let embedded = Embedded::new(&field.ident, &field.ty, inner)?;
+                let explicit_column = column.is_some();
+                let column = column.unwrap_or_else(|| embedded.inner.unraw().to_string());
+                let renamed = self.rename_all.is_some() && !explicit_column;
+                let column = match self.rename_all {
+                    Some(rename_all) if renamed => {
+                        rename_all.apply(&format!("{}{}", prefix, column))
+                    }
+                    _ => format!("{}{}", prefix, column),
+                };
+                fields.push(PatchField {
+                    ident: embedded.ident(),
+                    column,
+                    explicit_column,
+                    renamed,
+                    ty: embedded.column_ty(),
+                    custom_type,
+                    json: false,
+                    flatten: None,
+                    embedded: Some(embedded),
+                });
+            }
+        }
+        self.fields = fields;
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Expr, Ident, LitStr, Pat, Path, Result};
+
+use crate::backend::{Backend, Implementation};
+use splice::Spliced;
+
+mod parse;
+pub mod splice;
+
+/// The macros expanding to a conditional query
+#[derive(Copy, Clone)]
+pub enum Macro {
+    QueryAs,
+    Query,
+    QueryScalar,
+    Execute,
+}
+
+impl Macro {
+    fn path(self) -> TokenStream {
+        match self {
+            Macro::QueryAs => quote!(ormx::conditional_query_as),
+            Macro::Query => quote!(ormx::conditional_query),
+            Macro::QueryScalar => quote!(ormx::conditional_query_scalar),
+            Macro::Execute => quote!(ormx::conditional_execute),
+        }
+    }
+}
+
+pub struct ConditionalQuery {
+    pub output: Output,
+    pub fragments: Vec<Fragment>,
+}
+
+/// What the rows returned by a conditional query are mapped to
+pub enum Output {
+    // conditional_query_as!(<path>, ..)
+    Struct(Path),
+    // conditional_query!(..)
+    Record,
+    // conditional_query_scalar!(..)
+    Scalar,
+    // conditional_execute!(..)
+    Nothing,
+}
+
+pub enum Fragment {
+    // <string>
+    Sql(LitStr),
+    // ?(<expr>)
+    Arg(Expr),
+    // ?*(<expr>)
+    List(Expr),
+    // @columns(<path>), after the column list has been spliced in
+    Columns(Columns),
+    // <pat> = <expr> => { <fragment>* } [else { <fragment>* }]?
+    Condition(Condition),
+    // match <expr> { [<pat> => { <fragment>* }],* }
+    Match(Match),
+}
+
+pub struct Columns {
+    // path of the table
+    pub path: Path,
+    // struct `sqlx::query_as!` reads the rows of the table into, next to the table
+    pub query_as: Ident,
+    // column list
+    pub sql: LitStr,
+}
+
+pub struct Condition {
+    pub pat: Pat,
+    pub expr: Expr,
+    pub then: Vec<Fragment>,
+    pub otherwise: Vec<Fragment>,
+}
+
+pub struct Match {
+    pub expr: Expr,
+    pub arms: Vec<(Pat, Vec<Fragment>)>,
+}
+
+pub fn expand(input: TokenStream, kind: Macro) -> Result<TokenStream> {
+    let input = match splice::splice(input, kind.path())? {
+        Spliced::Done(input) => input,
+        Spliced::Pending(call) => return Ok(call),
+    };
+    let parsed = ConditionalQuery::parse(input, kind)?;
+    Ok(Implementation::impl_conditional_query(&parsed))
+}
@@ -0,0 +1,127 @@
+use proc_macro2::TokenStream;
+use syn::parse::{Parse, ParseStream, Parser};
+use syn::{Error, Expr, Ident, Pat, Result, Token};
+
+use super::{Columns, Condition, ConditionalQuery, Fragment, Macro, Match, Output};
+
+impl ConditionalQuery {
+    pub fn parse(input: TokenStream, kind: Macro) -> Result<Self> {
+        let parser = |input: ParseStream| {
+            let output = match kind {
+                Macro::QueryAs => {
+                    let out = input.parse()?;
+                    input.parse::<Token![,]>()?;
+                    Output::Struct(out)
+                }
+                Macro::Query => Output::Record,
+                Macro::QueryScalar => Output::Scalar,
+                Macro::Execute => Output::Nothing,
+            };
+            Ok(ConditionalQuery {
+                output,
+                fragments: parse_fragments(input)?,
+            })
+        };
+        parser.parse2(input)
+    }
+}
+
+fn parse_fragments(input: ParseStream) -> Result<Vec<Fragment>> {
+    let mut fragments = vec![];
+    while !input.is_empty() {
+        fragments.push(input.parse()?);
+    }
+    Ok(fragments)
+}
+
+fn parse_block(input: ParseStream) -> Result<Vec<Fragment>> {
+    let content;
+    syn::braced!(content in input);
+    parse_fragments(&content)
+}
+
+impl Parse for Fragment {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(syn::LitStr) {
+            Ok(Fragment::Sql(input.parse()?))
+        } else if input.peek(Token![?]) {
+            input.parse::<Token![?]>()?;
+            let list = input.parse::<Option<Token![*]>>()?.is_some();
+            let content;
+            syn::parenthesized!(content in input);
+            match list {
+                true => Ok(Fragment::List(content.parse()?)),
+                false => Ok(Fragment::Arg(content.parse()?)),
+            }
+        } else if input.peek(Token![@]) {
+            input.parse().map(Fragment::Columns)
+        } else if input.peek(Token![match]) {
+            input.parse().map(Fragment::Match)
+        } else {
+            input.parse().map(Fragment::Condition)
+        }
+    }
+}
+
+impl Parse for Columns {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<Token![@]>()?;
+        match input.parse::<Ident>()? {
+            ident if ident == "__columns" => (),
+            ident => return Err(Error::new(ident.span(), "expected `@columns(<path>)`")),
+        }
+        let content;
+        syn::parenthesized!(content in input);
+        let path = content.parse()?;
+        content.parse::<Token![,]>()?;
+        Ok(Columns {
+            path,
+            query_as: content.parse()?,
+            sql: input.parse()?,
+        })
+    }
+}
+
+impl Parse for Condition {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let pat = input.parse::<Pat>()?;
+        input.parse::<Token![=]>()?;
+        let expr = Expr::parse_without_eager_brace(input)?;
+        input.parse::<Token![=>]>()?;
+        let then = parse_block(input)?;
+        let otherwise = if input.peek(Token![else]) {
+            input.parse::<Token![else]>()?;
+            parse_block(input)?
+        } else {
+            vec![]
+        };
+        Ok(Condition {
+            pat,
+            expr,
+            then,
+            otherwise,
+        })
+    }
+}
+
+impl Parse for Match {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<Token![match]>()?;
+        let expr = Expr::parse_without_eager_brace(input)?;
+        let content;
+        syn::braced!(content in input);
+        let mut arms = vec![];
+        while !content.is_empty() {
+            let pat = content.parse::<Pat>()?;
+            content.parse::<Token![=>]>()?;
+            arms.push((pat, parse_block(&content)?));
+            if !content.is_empty() {
+                content.parse::<Token![,]>()?;
+            }
+        }
+        if arms.is_empty() {
+            return Err(Error::new(input.span(), "expected at least one match arm"));
+        }
+        Ok(Match { expr, arms })
+    }
+}
@@ -0,0 +1,255 @@
+//! Splicing information about other types into conditional queries.
+//!
+//! sqlx only accepts string literals as queries, and a proc macro can't look at other items, so
+//! neither the column list of a table nor the variants of an enum deriving `OrderBy` can be
+//! referred to directly. Instead, these derives generate a hidden macro next to the type, which
+//! passes the required information back to the conditional query macro.
+//!
+//! The information is prepended to the input of the conditional query as `@__resolved <data>`,
+//! one for every marker (`@columns(..)`, `order_by!(..)`), in the order they appear in the query.
+//! The conditional query then calls the hidden macros one after another until every marker has
+//! been resolved, after which the markers are replaced by the information.
+//!
+//! `derive(Table)` and `derive(Patch)` resolve the fields of flattened structs the same way.
+
+use proc_macro2::{Delimiter, Group, Ident, Span, TokenStream, TokenTree};
+use quote::{quote, ToTokens};
+use syn::parse::{ParseStream, Parser};
+use syn::punctuated::Punctuated;
+use syn::{Error, Expr, LitStr, Path, Result, Token, Type};
+
+const RESOLVED: &str = "__resolved";
+
+/// Name of the hidden macro containing the column list of the table `ident`.
+pub fn columns_macro_ident(ident: &Ident) -> Ident {
+    Ident::new(&format!("__ormx_columns_{}", ident), Span::call_site())
+}
+
+/// Name of the hidden macro containing the variants of the enum `ident` deriving `OrderBy`.
+pub fn order_by_macro_ident(ident: &Ident) -> Ident {
+    Ident::new(&format!("__ormx_order_by_{}", ident), Span::call_site())
+}
+
+/// Name of the hidden macro containing the fields of the struct `ident` deriving `Embeddable`.
+pub fn embeddable_macro_ident(ident: &Ident) -> Ident {
+    Ident::new(&format!("__ormx_embeddable_{}", ident), Span::call_site())
+}
+
+/// Generates a hidden macro, which expands to `callback! { <before> <data> <after> }`.
+pub fn hidden_macro(ident: &Ident, data: TokenStream) -> TokenStream {
+    quote! {
+        #[doc(hidden)]
+        #[allow(unused_macros)]
+        macro_rules! #ident {
+            ([$($callback:tt)*] ($($before:tt)*) ($($after:tt)*)) => {
+                $($callback)*! { $($before)* #data $($after)* }
+            };
+        }
+        #[doc(hidden)]
+        #[allow(unused_imports)]
+        pub(crate) use #ident;
+    }
+}
+
+/// Generates the hidden macro of a table, passing its column list as a string literal together
+/// with the struct `sqlx::query_as!` reads its rows into as `(<string>, <struct>)`.
+/// Rows are then mapped to the table by the hidden function `__ormx_from_row` of the table.
+pub fn columns_macro(ident: &Ident, column_list: &str, query_as: &Ident) -> TokenStream {
+    hidden_macro(
+        &columns_macro_ident(ident),
+        quote!((#column_list, #query_as)),
+    )
+}
+
+/// Generates the hidden macro of an enum deriving `OrderBy`, passing its variants together with
+/// their ordering as `{ <variant> => <string>, .. }`.
+pub fn order_by_macro(ident: &Ident, variants: &[(Ident, String)]) -> TokenStream {
+    let (variants, order_by): (Vec<_>, Vec<_>) = variants.iter().cloned().unzip();
+    hidden_macro(
+        &order_by_macro_ident(ident),
+        quote!({ #( #variants => #order_by ),* }),
+    )
+}
+
+/// Generates the hidden macro of a struct deriving `Embeddable`, passing its fields together with
+/// their attributes as `{ <field>: <type>, .. }`.
+pub fn embeddable_macro(ident: &Ident, fields: TokenStream) -> TokenStream {
+    hidden_macro(&embeddable_macro_ident(ident), quote!({ #fields }))
+}
+
+/// Removes the information prepended to `input` by the hidden macros called so far.
+pub fn split_resolved(input: TokenStream) -> (Vec<TokenTree>, TokenStream) {
+    let mut tokens = input.into_iter().collect::<Vec<_>>();
+    let mut resolved = vec![];
+    while let [TokenTree::Punct(at), TokenTree::Ident(ident), data, ..] = &tokens[..] {
+        if at.as_char() != '@' || ident != RESOLVED {
+            break;
+        }
+        resolved.push(data.clone());
+        tokens.drain(..3);
+    }
+    (resolved, tokens.into_iter().collect())
+}
+
+/// Calls the hidden macro of the type at `path`, which will call `callback` again with the
+/// information prepended to `input`.
+pub fn call_hidden_macro(
+    mut path: Path,
+    macro_ident: impl FnOnce(&Ident) -> Ident,
+    resolved: &[TokenTree],
+    callback: TokenStream,
+    input: TokenStream,
+) -> Result<TokenStream> {
+    let last = path
+        .segments
+        .last_mut()
+        .ok_or_else(|| Error::new(Span::call_site(), "expected a path"))?;
+    if !last.arguments.is_empty() {
+        return Err(Error::new_spanned(
+            &last.arguments,
+            "unexpected generic arguments",
+        ));
+    }
+    last.ident = macro_ident(&last.ident);
+    let resolved = resolved.iter().map(|data| quote!(@__resolved #data));
+    Ok(quote! {
+        #path! { [#callback] (#( #resolved )* @__resolved) (#input) }
+    })
+}
+
+/// A marker within a conditional query, which has to be resolved using a hidden macro
+enum Marker {
+    // @columns(<path>)
+    Columns(Path),
+    // order_by!(<expr>: <path>)
+    OrderBy(Box<Expr>, Path),
+}
+
+/// Resolves the markers within the input of a conditional query.
+/// Returns either the input with all markers replaced, or a call to the hidden macro resolving the
+/// next marker, which will call `callback` again.
+pub fn splice(input: TokenStream, callback: TokenStream) -> Result<Spliced> {
+    let (resolved, input) = split_resolved(input);
+    let mut next = 0;
+    match substitute(input.clone(), &resolved, &mut next)? {
+        Ok(substituted) => Ok(Spliced::Done(substituted)),
+        Err(Marker::Columns(path)) => {
+            let call = call_hidden_macro(path, columns_macro_ident, &resolved, callback, input)?;
+            Ok(Spliced::Pending(call))
+        }
+        Err(Marker::OrderBy(_, path)) => {
+            let call = call_hidden_macro(path, order_by_macro_ident, &resolved, callback, input)?;
+            Ok(Spliced::Pending(call))
+        }
+    }
+}
+
+pub enum Spliced {
+    /// All markers have been replaced
+    Done(TokenStream),
+    /// A hidden macro has to be called to resolve the next marker
+    Pending(TokenStream),
+}
+
+/// Replaces the markers in `input` with the resolved information, or returns the first marker
+/// which has not been resolved yet.
+fn substitute(
+    input: TokenStream,
+    resolved: &[TokenTree],
+    next: &mut usize,
+) -> Result<std::result::Result<TokenStream, Marker>> {
+    let tokens = input.into_iter().collect::<Vec<_>>();
+    let mut output = TokenStream::new();
+    let mut idx = 0;
+    while idx < tokens.len() {
+        if let Some(marker) = parse_marker(&tokens[idx..])? {
+            idx += 3;
+            match resolved.get(*next) {
+                Some(data) => output.extend(replace(marker, data)?),
+                None => return Ok(Err(marker)),
+            }
+            *next += 1;
+            continue;
+        }
+        match &tokens[idx] {
+            TokenTree::Group(group) => {
+                let stream = match substitute(group.stream(), resolved, next)? {
+                    Ok(stream) => stream,
+                    Err(marker) => return Ok(Err(marker)),
+                };
+                let mut substituted = Group::new(group.delimiter(), stream);
+                substituted.set_span(group.span());
+                output.extend(Some(TokenTree::Group(substituted)));
+            }
+            other => output.extend(Some(other.clone())),
+        }
+        idx += 1;
+    }
+    Ok(Ok(output))
+}
+
+fn parse_marker(tokens: &[TokenTree]) -> Result<Option<Marker>> {
+    match tokens {
+        [TokenTree::Punct(at), TokenTree::Ident(ident), TokenTree::Group(group), ..]
+            if at.as_char() == '@'
+                && ident == "columns"
+                && group.delimiter() == Delimiter::Parenthesis =>
+        {
+            Ok(Some(Marker::Columns(syn::parse2(group.stream())?)))
+        }
+        [TokenTree::Ident(ident), TokenTree::Punct(bang), TokenTree::Group(group), ..]
+            if ident == "order_by"
+                && bang.as_char() == '!'
+                && group.delimiter() == Delimiter::Parenthesis =>
+        {
+            // `<expr>: <path>` may be parsed as a type ascription
+            match syn::parse2::<Expr>(group.stream())? {
+                Expr::Type(ascription) => match *ascription.ty {
+                    Type::Path(ty) if ty.qself.is_none() => {
+                        Ok(Some(Marker::OrderBy(ascription.expr, ty.path)))
+                    }
+                    ty => Err(Error::new_spanned(ty, "expected a path to an enum")),
+                },
+                other => Err(Error::new_spanned(
+                    other,
+                    "expected `order_by!(<expression>: <enum>)`",
+                )),
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Replaces a marker with the information resolved by its hidden macro.
+fn replace(marker: Marker, data: &TokenTree) -> Result<TokenStream> {
+    match marker {
+        Marker::Columns(path) => {
+            let parser = |input: ParseStream| {
+                let content;
+                syn::parenthesized!(content in input);
+                let column_list = content.parse::<LitStr>()?;
+                content.parse::<Token![,]>()?;
+                Ok((column_list, content.parse::<Ident>()?))
+            };
+            let (column_list, query_as) = parser.parse2(data.to_token_stream())?;
+            // the path is kept, so the rows can be mapped to the table
+            Ok(quote!(@__columns(#path, #query_as) #column_list))
+        }
+        Marker::OrderBy(expr, path) => {
+            let parser = |input: ParseStream| {
+                let content;
+                syn::braced!(content in input);
+                Punctuated::<(Ident, LitStr), Token![,]>::parse_terminated_with(&content, |i| {
+                    let variant = i.parse::<Ident>()?;
+                    i.parse::<Token![=>]>()?;
+                    Ok((variant, i.parse::<LitStr>()?))
+                })
+            };
+            let arms = parser.parse2(data.to_token_stream())?;
+            let arms = arms
+                .iter()
+                .map(|(variant, order_by)| quote!(#path::#variant => { "ORDER BY" #order_by }));
+            Ok(quote!(match #expr { #( #arms ),* }))
+        }
+    }
+}
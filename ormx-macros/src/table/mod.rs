@@ -2,11 +2,14 @@ use std::convert::TryFrom;
 
 use itertools::Itertools;
 use proc_macro2::{Ident, Span, TokenStream};
-use quote::quote;
-use syn::{DeriveInput, Result, Type, Visibility};
+use quote::{quote, ToTokens};
+use syn::ext::IdentExt;
+use syn::{Result, Type, Visibility};
 
-use crate::attrs::{Getter, Insertable};
+use crate::attrs::{Getter, Index, Insertable, RenameAll, Skip};
 use crate::backend::{Backend, Implementation};
+use crate::embeddable::{self, Embedded, Resolved};
+use crate::utils::{custom_type_arg, json_arg, option_inner, query, query_as, RUNTIME_QUERIES};
 use std::borrow::Cow;
 use std::marker::PhantomData;
 
@@ -16,9 +19,18 @@ pub struct Table<B: Backend> {
     pub ident: Ident,
     pub vis: Visibility,
     pub table: String,
+    /// The schema containing the table, declared using `#[ormx(schema = ..)]`.
+    pub schema: Option<String>,
     pub id: TableField<B>,
     pub fields: Vec<TableField<B>>,
     pub insertable: Option<Insertable>,
+    pub ddl: bool,
+    pub indexes: Vec<Index>,
+    /// The doc comment of the struct, used as the comment of the table.
+    pub comment: Option<String>,
+    /// Fields annotated with `#[ormx(skip)]`, which are not part of `fields`.
+    pub skipped: Vec<TableField<B>>,
+    pub rename_all: Option<RenameAll>,
 }
 
 #[derive(Clone)]
@@ -26,16 +38,110 @@ pub struct TableField<B: Backend> {
     pub field: Ident,
     pub ty: Type,
     pub column_name: String,
+    /// Whether the column is named using `#[ormx(column = ..)]`, which takes precedence over
+    /// `#[ormx(rename_all = ..)]`.
+    pub explicit_column: bool,
+    /// Whether the column is named using `#[ormx(rename_all = ..)]`.
+    pub renamed: bool,
     pub custom_type: bool,
-    pub reserved_ident: bool,
+    /// Whether the field is annotated with `#[ormx(json)]`, so it is wrapped in
+    /// `sqlx::types::Json` when reading and writing it.
+    pub json: bool,
+    pub read_only: bool,
     pub default: bool,
+    pub default_expr: Option<String>,
+    pub sql_type: Option<String>,
+    pub primary_key: bool,
+    pub auto_increment: bool,
+    pub identity: Option<Identity>,
+    pub unique: bool,
+    pub references: Option<ForeignKey>,
+    pub renamed_from: Option<String>,
+    /// The doc comment of the field, used as the comment of the column.
+    pub comment: Option<String>,
+    pub skip: Option<Skip>,
+    /// The prefix of the columns of a field annotated with `#[ormx(flatten)]`, which is replaced
+    /// by its columns once the embeddable struct has been resolved.
+    pub flatten: Option<String>,
+    pub embedded: Option<Embedded>,
     pub get_one: Option<Getter>,
     pub get_optional: Option<Getter>,
     pub get_many: Option<Getter>,
+    pub get_range: Option<Getter>,
     pub set: Option<Ident>,
     pub _phantom: PhantomData<*const B>,
 }
 
+/// A foreign key, declared using `#[ormx(references = "<table>(<column>)")]`.
+#[derive(Clone)]
+pub struct ForeignKey {
+    pub table: String,
+    pub column: String,
+    pub on_delete: Option<ReferentialAction>,
+    pub on_update: Option<ReferentialAction>,
+}
+
+/// An identity column on postgres, declared using `#[ormx(identity = ..)]`.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Identity {
+    ByDefault,
+    Always,
+}
+
+impl Identity {
+    pub const ALL: &'static [(&'static str, Identity)] = &[
+        ("by_default", Identity::ByDefault),
+        ("always", Identity::Always),
+    ];
+
+    pub fn sql(self) -> &'static str {
+        match self {
+            Identity::ByDefault => "GENERATED BY DEFAULT AS IDENTITY",
+            Identity::Always => "GENERATED ALWAYS AS IDENTITY",
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum ReferentialAction {
+    Cascade,
+    Restrict,
+    SetNull,
+    SetDefault,
+    NoAction,
+}
+
+impl ReferentialAction {
+    pub const ALL: &'static [(&'static str, ReferentialAction)] = &[
+        ("cascade", ReferentialAction::Cascade),
+        ("restrict", ReferentialAction::Restrict),
+        ("set_null", ReferentialAction::SetNull),
+        ("set_default", ReferentialAction::SetDefault),
+        ("no_action", ReferentialAction::NoAction),
+    ];
+
+    pub fn sql(self) -> &'static str {
+        match self {
+            ReferentialAction::Cascade => "CASCADE",
+            ReferentialAction::Restrict => "RESTRICT",
+            ReferentialAction::SetNull => "SET NULL",
+            ReferentialAction::SetDefault => "SET DEFAULT",
+            ReferentialAction::NoAction => "NO ACTION",
+        }
+    }
+
+    /// Returns the corresponding variant of `ormx::ReferentialAction`.
+    pub fn variant(self) -> TokenStream {
+        match self {
+            ReferentialAction::Cascade => quote!(ormx::ReferentialAction::Cascade),
+            ReferentialAction::Restrict => quote!(ormx::ReferentialAction::Restrict),
+            ReferentialAction::SetNull => quote!(ormx::ReferentialAction::SetNull),
+            ReferentialAction::SetDefault => quote!(ormx::ReferentialAction::SetDefault),
+            ReferentialAction::NoAction => quote!(ormx::ReferentialAction::NoAction),
+        }
+    }
+}
+
 impl<B: Backend> Table<B> {
     pub fn fields_except_id(&self) -> impl Iterator<Item = &TableField<B>> + Clone {
         let id = self.id.field.clone();
@@ -43,11 +149,189 @@ impl<B: Backend> Table<B> {
     }
 
     pub fn insertable_fields(&self) -> impl Iterator<Item = &TableField<B>> + Clone {
-        self.fields_except_id().filter(|field| !field.default)
+        self.fields_except_id()
+            .filter(|field| !field.default && field.writable())
     }
 
+    /// Returns the fields which are generated by the database when inserting a row.
     pub fn default_fields(&self) -> impl Iterator<Item = &TableField<B>> + Clone {
-        self.fields.iter().filter(|field| field.default)
+        self.fields_except_id()
+            .filter(|field| field.default || !field.writable())
+    }
+
+    /// Returns the fields written by `Table::update`.
+    pub fn updatable_fields(&self) -> impl Iterator<Item = &TableField<B>> + Clone {
+        self.fields_except_id().filter(|field| field.writable())
+    }
+
+    /// Returns the fields which are never written, and are refreshed after writing a row.
+    pub fn read_only_fields(&self) -> impl Iterator<Item = &TableField<B>> + Clone {
+        self.fields_except_id().filter(|field| !field.writable())
+    }
+
+    /// Returns the fields annotated with `#[ormx(primary_key)]`, or the ID if there are none.
+    pub fn primary_key(&self) -> Vec<&TableField<B>> {
+        let fields = self
+            .fields
+            .iter()
+            .filter(|field| field.primary_key)
+            .collect::<Vec<_>>();
+        match fields.is_empty() {
+            true => vec![&self.id],
+            false => fields,
+        }
+    }
+
+    pub fn field(&self, ident: &Ident) -> &TableField<B> {
+        self.fields
+            .iter()
+            .find(|field| &field.field == ident)
+            .expect("not a field of the table")
+    }
+
+    /// Returns the name of the hidden struct rows are read into by `sqlx::query_as!` if some
+    /// fields are skipped or flattened, since the macro can only construct structs from columns.
+    pub fn row_ident(&self) -> Ident {
+        Ident::new(&format!("__OrmxRow{}", self.ident), Span::call_site())
+    }
+
+    /// Returns the struct `sqlx::query_as!` reads rows of this table into.
+    pub fn query_as_ident(&self) -> Ident {
+        match self.needs_row_struct() && !RUNTIME_QUERIES {
+            true => self.row_ident(),
+            false => self.ident.clone(),
+        }
+    }
+
+    /// Returns true if `sqlx::query_as!` can't read rows into the table itself.
+    pub fn needs_row_struct(&self) -> bool {
+        !self.skipped.is_empty()
+            || self
+                .fields
+                .iter()
+                .any(|field| field.embedded.is_some() || field.json)
+    }
+
+    /// Generates a query returning rows of this table, see [query_as].
+    /// If some fields are skipped, flattened or stored as JSON, the macros read the rows into the
+    /// hidden struct, which is then mapped to the table. With `runtime-queries`, the rows are read
+    /// by the hidden function generated by [impl_from_row](crate::backend::Backend::impl_from_row),
+    /// so the table doesn't have to implement `FromRow`.
+    pub fn query_as_self(&self, sql: &str, args: &[TokenStream]) -> TokenStream {
+        if RUNTIME_QUERIES {
+            let query = query(sql, args);
+            return quote! {
+                #query.try_map(
+                    |row: <ormx::Db as sqlx::Database>::Row| Self::__ormx_from_row(&row)
+                )
+            };
+        }
+        if !self.needs_row_struct() {
+            return query_as(&quote!(Self), sql, args);
+        }
+        let query = query_as(&self.row_ident().into_token_stream(), sql, args);
+        let fields = Self::init_fields(self.fields.iter(), |field| {
+            let ident = &field.field;
+            field.unwrap_json(quote!(row.#ident))
+        });
+        let skipped = self.skipped_fields();
+        quote! {
+            #query.map(|row| Self {
+                #fields
+                #skipped
+            })
+        }
+    }
+
+    /// Initializes the fields of the struct from the values of the given columns, e.g.
+    /// `email: row.email,`. The columns of a flattened field are combined into its embeddable
+    /// struct.
+    pub fn init_fields<'a>(
+        columns: impl Iterator<Item = &'a TableField<B>>,
+        value: impl Fn(&TableField<B>) -> TokenStream,
+    ) -> TokenStream
+    where
+        B: 'a,
+    {
+        let mut fields = TokenStream::new();
+        for (ident, group) in &columns.group_by(|field| field.struct_field()) {
+            let group = group.collect::<Vec<_>>();
+            let value = match group[0].embedded {
+                Some(_) => {
+                    let columns = group
+                        .iter()
+                        .map(|field| (field.embedded.as_ref().unwrap(), value(field)))
+                        .collect::<Vec<_>>();
+                    embeddable::construct(&columns)
+                }
+                None => value(group[0]),
+            };
+            fields.extend(quote!(#ident: #value,));
+        }
+        fields
+    }
+
+    /// Returns the fields of the struct the given columns are mapped to, together with their
+    /// types. Flattened fields are only returned once.
+    pub fn struct_fields<'a>(
+        columns: impl Iterator<Item = &'a TableField<B>>,
+    ) -> Vec<(&'a Ident, TokenStream)>
+    where
+        B: 'a,
+    {
+        columns
+            .map(|field| match &field.embedded {
+                Some(embedded) => {
+                    let path = &embedded.path;
+                    match embedded.optional {
+                        true => (&embedded.field, quote!(Option<#path>)),
+                        false => (&embedded.field, quote!(#path)),
+                    }
+                }
+                None => {
+                    let ty = &field.ty;
+                    (&field.field, quote!(#ty))
+                }
+            })
+            .dedup_by(|(a, _), (b, _)| a == b)
+            .collect()
+    }
+
+    /// Initializes the skipped fields, e.g. `display_name: Default::default(),`.
+    pub fn skipped_fields(&self) -> TokenStream {
+        self.skipped
+            .iter()
+            .map(|field| {
+                let ident = &field.field;
+                match field.skip.as_ref().and_then(|skip| skip.default.as_ref()) {
+                    Some(default) => quote!(#ident: #default,),
+                    None => quote!(#ident: Default::default(),),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the name of the table as used within queries, qualified by its schema and quoted
+    /// if required.
+    pub fn qualified_table(&self) -> String {
+        B::qualified_name(self.schema.as_deref(), &self.table)
+    }
+
+    /// Returns the name of an index as stored by the database, defaulting to
+    /// `<table>_<columns>_idx`, or `_key` if unique.
+    pub fn index_name(&self, index: &Index) -> String {
+        match &index.name {
+            Some(name) => name.clone(),
+            None => {
+                let columns = index
+                    .columns
+                    .iter()
+                    .map(|column| self.field(column).stored_name())
+                    .join("_");
+                let suffix = if index.unique { "key" } else { "idx" };
+                format!("{}_{}_{}", B::stored_name(&self.table), columns, suffix)
+            }
+        }
     }
 
     pub fn select_column_list(&self) -> String {
@@ -59,37 +343,142 @@ impl<B: Backend> Table<B> {
 }
 
 impl<B: Backend> TableField<B> {
+    /// Returns false if the field is never written, since it is annotated with
+    /// `#[ormx(read_only)]` or the database rejects writing to it (`GENERATED ALWAYS`).
+    pub fn writable(&self) -> bool {
+        !self.read_only && self.identity != Some(Identity::Always)
+    }
+
+    /// Returns the field of the struct this column is mapped to, which is the flattened field
+    /// for the columns of an embeddable struct.
+    pub fn struct_field(&self) -> &Ident {
+        match &self.embedded {
+            Some(embedded) => &embedded.field,
+            None => &self.field,
+        }
+    }
+
+    /// Returns the name of the field this column is mapped to, e.g. `address.street`.
+    pub fn field_name(&self) -> String {
+        match &self.embedded {
+            Some(embedded) => format!("{}.{}", embedded.field.unraw(), embedded.inner.unraw()),
+            None => self.field.unraw().to_string(),
+        }
+    }
+
+    /// Returns the value of this column within `base`, e.g. `self.email`.
+    pub fn value(&self, base: TokenStream) -> TokenStream {
+        match &self.embedded {
+            Some(embedded) => embedded.value(base),
+            None => {
+                let ident = &self.field;
+                quote!(#base.#ident)
+            }
+        }
+    }
+
+    /// Returns the value of this column within `base`, passed as an argument of a query.
+    pub fn arg(&self, base: TokenStream) -> TokenStream {
+        self.bind(self.value(base))
+    }
+
+    /// Passes `value`, a value of this column, as an argument of a query.
+    pub fn bind(&self, value: TokenStream) -> TokenStream {
+        match (self.custom_type, &self.embedded) {
+            _ if self.json => json_arg(value, &self.ty),
+            (false, _) => value,
+            (true, Some(embedded)) => custom_type_arg(value, &embedded.value_ty()),
+            (true, None) => custom_type_arg(value, &self.ty),
+        }
+    }
+
+    /// Returns the type this column is read as, which is wrapped in `sqlx::types::Json` for
+    /// fields annotated with `#[ormx(json)]`.
+    pub fn row_ty(&self) -> TokenStream {
+        let ty = &self.ty;
+        match (self.json, option_inner(ty)) {
+            (false, _) => quote!(#ty),
+            (true, Some(inner)) => quote!(Option<sqlx::types::Json<#inner>>),
+            (true, None) => quote!(sqlx::types::Json<#ty>),
+        }
+    }
+
+    /// Converts `value`, read as [TableField::row_ty], into the type of the field.
+    pub fn unwrap_json(&self, value: TokenStream) -> TokenStream {
+        match (self.json, self.is_nullable()) {
+            (false, _) => value,
+            (true, true) => quote!(#value.map(|json| json.0)),
+            (true, false) => quote!(#value.0),
+        }
+    }
+
+    /// Returns the name this column is returned under when selected using [Self::fmt_for_select].
+    pub fn select_label(&self) -> String {
+        let field = self.field.unraw().to_string();
+        match (self.custom_type || self.json) && !RUNTIME_QUERIES {
+            true => format!("{}: _", field),
+            false => field,
+        }
+    }
+
     pub fn fmt_for_select(&self) -> String {
-        if self.custom_type {
-            format!(
-                "{} AS {}{}: _{}",
-                self.column(),
-                B::QUOTE,
-                self.field,
-                B::QUOTE
-            )
-        } else if self.field == self.column_name {
+        let field = self.field.unraw().to_string();
+        // the type override is only understood by the `sqlx::query*!` macros
+        if (self.custom_type || self.json) && !RUNTIME_QUERIES {
+            format!("{} AS {}{}: _{}", self.column(), B::QUOTE, field, B::QUOTE)
+        } else if field == self.column_name {
             self.column().into()
         } else {
-            format!("{} AS {}", self.column(), self.field)
+            format!("{} AS {}", self.column(), B::quote_ident(&field))
+        }
+    }
+
+    /// Returns an expression evaluating to the SQL type of this column, which is inferred from the
+    /// type of the field unless `#[ormx(sql_type = ..)]` is given.
+    pub fn sql_type(&self) -> TokenStream {
+        let ty = &self.ty;
+        match &self.sql_type {
+            Some(sql_type) => quote!(#sql_type),
+            None if self.json => {
+                let ty = option_inner(ty).unwrap_or(ty);
+                quote!(<sqlx::types::Json<#ty> as ormx::SqlType>::SQL_TYPE)
+            }
+            None => quote!(<#ty as ormx::SqlType>::SQL_TYPE),
         }
     }
 
+    /// Returns true if the type of this field is an `Option`, so the column may be NULL.
+    pub fn is_nullable(&self) -> bool {
+        option_inner(&self.ty).is_some()
+    }
+
     pub fn column<'a>(&'a self) -> Cow<'a, str> {
-        if self.reserved_ident {
-            format!("{}{}{}", B::QUOTE, self.column_name, B::QUOTE).into()
-        } else {
-            Cow::Borrowed(&self.column_name)
+        match self.renamed {
+            true => B::quote_renamed(&self.column_name),
+            false => B::quote_ident(&self.column_name),
         }
     }
+
+    /// Returns the name of the column as stored by the database.
+    pub fn stored_name(&self) -> String {
+        B::stored_name(&self.column())
+    }
 }
 
 impl Getter {
     pub fn or_fallback<B: Backend>(&self, field: &TableField<B>) -> (Ident, Type) {
+        self.or_fallback_named(field, &format!("by_{}", field.field.unraw()))
+    }
+
+    pub fn or_fallback_named<B: Backend>(
+        &self,
+        field: &TableField<B>,
+        default_name: &str,
+    ) -> (Ident, Type) {
         let ident = self
             .func
             .clone()
-            .unwrap_or_else(|| Ident::new(&format!("by_{}", field.field), Span::call_site()));
+            .unwrap_or_else(|| Ident::new(default_name, Span::call_site()));
         let arg = self.arg_ty.clone().unwrap_or_else(|| {
             let ty = &field.ty;
             syn::parse2(quote!(&#ty)).unwrap()
@@ -98,10 +487,18 @@ impl Getter {
     }
 }
 
-pub fn derive(input: DeriveInput) -> Result<TokenStream> {
-    let parsed = Table::try_from(&input)?;
+pub fn derive(input: TokenStream) -> Result<TokenStream> {
+    let (embeddables, input) = match embeddable::resolve(input, quote!(ormx::__derive_table))? {
+        Resolved::Done(embeddables, input) => (embeddables, *input),
+        Resolved::Pending(call) => return Ok(call),
+    };
+    let mut parsed = Table::try_from(&input)?;
+    parsed.flatten(embeddables)?;
 
     let impl_table = Implementation::impl_table(&parsed);
+    let impl_from_row = Implementation::impl_from_row(&parsed);
+    let impl_table_meta = Implementation::impl_table_meta(&parsed);
+    let impl_schema = Implementation::impl_schema(&parsed);
     let insert_struct = Implementation::insert_struct(&parsed);
     let impl_insert = Implementation::impl_insert(&parsed);
     let getters = Implementation::impl_getters(&parsed);
@@ -109,6 +506,9 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream> {
 
     Ok(quote! {
         #impl_table
+        #impl_from_row
+        #impl_table_meta
+        #impl_schema
         #insert_struct
         #impl_insert
         #getters
@@ -1,12 +1,14 @@
 use std::convert::TryFrom;
 
 use proc_macro2::Span;
-use syn::{Data, DeriveInput, Error, Ident, Result};
+use syn::ext::IdentExt;
+use syn::{Data, DeriveInput, Error, FieldsNamed, Ident, LitStr, Result};
 
-use crate::attrs::{parse_attrs, Insertable, TableAttr, TableFieldAttr};
-use crate::utils::{missing_attr, set_once};
+use crate::attrs::{parse_attrs, Index, Insertable, TableAttr, TableFieldAttr};
+use crate::embeddable::Embedded;
+use crate::utils::{doc_comment, missing_attr, set_once, split_schema};
 
-use super::{Table, TableField};
+use super::{ForeignKey, Identity, ReferentialAction, Table, TableField};
 use crate::backend::Backend;
 use std::marker::PhantomData;
 
@@ -20,54 +22,238 @@ impl<B: Backend> TryFrom<&syn::Field> for TableField<B> {
     fn try_from(value: &syn::Field) -> Result<Self> {
         let ident = value.ident.clone().unwrap();
 
-        let reserved_ident = B::RESERVED_IDENTS.contains(&&*ident.to_string().to_uppercase());
-        if reserved_ident {
-            proc_macro_error::emit_warning!(
-                ident.span(),
-                "This is a reserved keyword, you might want to consider choosing a different name."
-            );
-        }
-
         none!(
             column,
             custom_type,
+            json,
             get_one,
             get_optional,
             get_many,
+            get_range,
             set,
-            default
+            read_only,
+            default,
+            sql_type,
+            primary_key,
+            auto_increment,
+            identity,
+            unique,
+            references,
+            on_delete,
+            on_update,
+            renamed_from,
+            skip,
+            flatten,
+            prefix
         );
 
-        for attr in parse_attrs::<TableFieldAttr>(&value.attrs)? {
+        let attrs = parse_attrs::<TableFieldAttr>(&value.attrs)?;
+        let attr_count = attrs.len();
+        for attr in attrs {
             match attr {
                 TableFieldAttr::Column(c) => set_once(&mut column, c)?,
                 TableFieldAttr::CustomType(..) => set_once(&mut custom_type, true)?,
+                TableFieldAttr::Json(..) => set_once(&mut json, true)?,
                 TableFieldAttr::GetOne(g) => set_once(&mut get_one, g)?,
                 TableFieldAttr::GetOptional(g) => set_once(&mut get_optional, g)?,
                 TableFieldAttr::GetMany(g) => set_once(&mut get_many, g)?,
+                TableFieldAttr::GetRange(g) => set_once(&mut get_range, g)?,
                 TableFieldAttr::Set(s) => {
-                    let default = || Ident::new(&format!("set_{}", ident), Span::call_site());
+                    let default =
+                        || Ident::new(&format!("set_{}", ident.unraw()), Span::call_site());
                     set_once(&mut set, s.unwrap_or_else(default))?
                 }
-                TableFieldAttr::Default(..) => set_once(&mut default, true)?,
+                TableFieldAttr::ReadOnly(..) => set_once(&mut read_only, true)?,
+                TableFieldAttr::Skip(s) => set_once(&mut skip, s)?,
+                TableFieldAttr::Default(expr) => set_once(&mut default, expr)?,
+                TableFieldAttr::SqlType(ty) => set_once(&mut sql_type, ty)?,
+                TableFieldAttr::PrimaryKey(..) => set_once(&mut primary_key, true)?,
+                TableFieldAttr::AutoIncrement(..) => set_once(&mut auto_increment, true)?,
+                TableFieldAttr::Identity(i) => set_once(&mut identity, parse_identity::<B>(&i)?)?,
+                TableFieldAttr::Unique(..) => set_once(&mut unique, true)?,
+                TableFieldAttr::References(r) => set_once(&mut references, r)?,
+                TableFieldAttr::OnDelete(a) => set_once(&mut on_delete, parse_action(&a)?)?,
+                TableFieldAttr::OnUpdate(a) => set_once(&mut on_update, parse_action(&a)?)?,
+                TableFieldAttr::RenamedFrom(c) => set_once(&mut renamed_from, c)?,
+                TableFieldAttr::Flatten(..) => set_once(&mut flatten, ())?,
+                TableFieldAttr::Prefix(p) => set_once(&mut prefix, p)?,
+            }
+        }
+
+        match flatten {
+            Some(_) if attr_count > 1 + prefix.is_some() as usize => {
+                return Err(Error::new(
+                    ident.span(),
+                    "#[ormx(flatten)] can only be combined with #[ormx(prefix = ..)]",
+                ))
+            }
+            None if prefix.is_some() => {
+                return Err(Error::new(
+                    ident.span(),
+                    "#[ormx(prefix = ..)] requires #[ormx(flatten)]",
+                ))
             }
+            _ => {}
+        }
+
+        if skip.is_some() && attr_count > 1 {
+            return Err(Error::new(
+                ident.span(),
+                "#[ormx(skip)] can't be combined with other attributes",
+            ));
+        }
+
+        let reserved_ident = B::RESERVED_IDENTS.contains(&&*ident.to_string().to_uppercase());
+        if reserved_ident && skip.is_none() {
+            proc_macro_error::emit_warning!(
+                ident.span(),
+                "This is a reserved keyword, you might want to consider choosing a different name."
+            );
         }
+
+        if get_one.iter().chain(&get_optional).any(|g| g.limit) {
+            return Err(Error::new(
+                ident.span(),
+                "limit is only supported by #[ormx(get_many)] and #[ormx(get_range)]",
+            ));
+        }
+
+        if identity.is_some() && (auto_increment.is_some() || default.is_some()) {
+            return Err(Error::new(
+                ident.span(),
+                "identity columns are generated by the database, so they can't be annotated \
+                 with #[ormx(auto_increment)] or #[ormx(default)]",
+            ));
+        }
+        if identity == Some(Identity::Always) && set.is_some() {
+            return Err(Error::new(
+                ident.span(),
+                "columns generated always can't be set, use #[ormx(identity = by_default)]",
+            ));
+        }
+        if json.is_some() {
+            let conflicting = [
+                ("custom_type", custom_type.is_some()),
+                ("default", default.is_some()),
+                ("read_only", read_only.is_some()),
+                ("get_one", get_one.is_some()),
+                ("get_optional", get_optional.is_some()),
+                ("get_many", get_many.is_some()),
+                ("get_range", get_range.is_some()),
+            ];
+            if let Some((attr, _)) = conflicting.iter().find(|(_, present)| *present) {
+                return Err(Error::new(
+                    ident.span(),
+                    format!("#[ormx(json)] can't be combined with #[ormx({})]", attr),
+                ));
+            }
+        }
+        if read_only.is_some() && set.is_some() {
+            return Err(Error::new(
+                ident.span(),
+                "#[ormx(read_only)] fields can't be set",
+            ));
+        }
+
+        let references = match references {
+            Some(references) => Some(parse_references(&references, on_delete, on_update)?),
+            None if on_delete.is_some() || on_update.is_some() => {
+                return Err(Error::new(
+                    ident.span(),
+                    "on_delete and on_update require #[ormx(references = ..)]",
+                ))
+            }
+            None => None,
+        };
+
         Ok(TableField {
-            column_name: column.unwrap_or_else(|| ident.to_string()),
+            explicit_column: column.is_some(),
+            renamed: false,
+            column_name: column.unwrap_or_else(|| ident.unraw().to_string()),
             field: ident,
             ty: value.ty.clone(),
             custom_type: custom_type.unwrap_or(false),
-            reserved_ident,
-            default: default.unwrap_or(false),
+            json: json.unwrap_or(false),
+            read_only: read_only.unwrap_or(false),
+            default: default.is_some(),
+            default_expr: default.flatten(),
+            sql_type,
+            primary_key: primary_key.unwrap_or(false),
+            auto_increment: auto_increment.unwrap_or(false),
+            identity,
+            unique: unique.unwrap_or(false),
+            references,
+            renamed_from,
+            comment: doc_comment(&value.attrs),
+            skip,
+            flatten: flatten.map(|_| prefix.unwrap_or_default()),
+            embedded: None,
             get_one,
             get_optional,
             get_many,
+            get_range,
             set,
             _phantom: PhantomData,
         })
     }
 }
 
+/// Parses `<table>(<column>)`.
+fn parse_references(
+    references: &LitStr,
+    on_delete: Option<ReferentialAction>,
+    on_update: Option<ReferentialAction>,
+) -> Result<ForeignKey> {
+    let value = references.value();
+    let parsed = value
+        .trim()
+        .strip_suffix(')')
+        .and_then(|references| references.split_once('('));
+    match parsed {
+        Some((table, column)) if !table.trim().is_empty() && !column.trim().is_empty() => {
+            Ok(ForeignKey {
+                table: table.trim().to_owned(),
+                column: column.trim().to_owned(),
+                on_delete,
+                on_update,
+            })
+        }
+        _ => Err(Error::new(
+            references.span(),
+            "expected #[ormx(references = \"<table>(<column>)\")]",
+        )),
+    }
+}
+
+fn parse_identity<B: Backend>(identity: &Ident) -> Result<Identity> {
+    if !B::IDENTITY {
+        return Err(Error::new(
+            identity.span(),
+            "#[ormx(identity)] is only supported by postgres, use #[ormx(auto_increment)] instead",
+        ));
+    }
+    Identity::ALL
+        .iter()
+        .find(|(name, _)| identity == name)
+        .map(|(_, identity)| *identity)
+        .ok_or_else(|| Error::new(identity.span(), "expected by_default or always"))
+}
+
+fn parse_action(action: &Ident) -> Result<ReferentialAction> {
+    ReferentialAction::ALL
+        .iter()
+        .find(|(name, _)| action == name)
+        .map(|(_, action)| *action)
+        .ok_or_else(|| {
+            let expected = ReferentialAction::ALL.iter().map(|(name, _)| *name);
+            let message = format!(
+                "expected one of {}",
+                expected.collect::<Vec<_>>().join(", ")
+            );
+            Error::new(action.span(), message)
+        })
+}
+
 impl<B: Backend> TryFrom<&syn::DeriveInput> for Table<B> {
     type Error = Error;
 
@@ -77,16 +263,20 @@ impl<B: Backend> TryFrom<&syn::DeriveInput> for Table<B> {
             _ => panic!("not a struct with named fields"),
         };
 
-        let fields = data
+        let (skipped, mut fields): (Vec<_>, Vec<_>) = data
             .fields
             .iter()
             .map(TableField::try_from)
-            .collect::<Result<Vec<_>>>()?;
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .partition(|field: &TableField<B>| field.skip.is_some());
 
-        none!(table, id, insertable);
+        none!(table, schema, id, insertable, ddl, rename_all);
+        let mut indexes = vec![];
         for attr in parse_attrs::<TableAttr>(&value.attrs)? {
             match attr {
                 TableAttr::Table(x) => set_once(&mut table, x)?,
+                TableAttr::Schema(x) => set_once(&mut schema, x)?,
                 TableAttr::Id(x) => set_once(&mut id, x)?,
                 TableAttr::Insertable(x) => {
                     let default = || Insertable {
@@ -95,9 +285,22 @@ impl<B: Backend> TryFrom<&syn::DeriveInput> for Table<B> {
                     };
                     set_once(&mut insertable, x.unwrap_or_else(default))?;
                 }
+                TableAttr::Ddl(..) => set_once(&mut ddl, true)?,
+                TableAttr::Index(x) => indexes.push(x),
+                TableAttr::RenameAll(x) => set_once(&mut rename_all, x)?,
+            }
+        }
+
+        if let Some(rename_all) = rename_all {
+            for field in fields.iter_mut().filter(|field| !field.explicit_column) {
+                field.column_name = rename_all.apply(&field.column_name);
+                field.renamed = true;
             }
         }
 
+        let table = table.ok_or_else(|| missing_attr("table"))?;
+        let (schema, table) = split_schema(schema, table);
+
         let id = id.ok_or_else(|| missing_attr("id"))?;
         let id = fields
             .iter()
@@ -124,13 +327,154 @@ impl<B: Backend> TryFrom<&syn::DeriveInput> for Table<B> {
             ));
         }
 
+        let ddl = ddl.unwrap_or(false);
+        for field in &fields {
+            let ddl_only = [
+                ("sql_type", field.sql_type.is_some()),
+                ("primary_key", field.primary_key),
+                ("auto_increment", field.auto_increment),
+                ("default = ..", field.default_expr.is_some()),
+                ("unique", field.unique),
+                ("renamed_from", field.renamed_from.is_some()),
+            ];
+            match ddl_only.iter().find(|(_, present)| *present) {
+                Some((attr, _)) if !ddl => {
+                    return Err(Error::new(
+                        field.field.span(),
+                        format!("#[ormx({})] has no effect without #[ormx(ddl)]", attr),
+                    ))
+                }
+                _ => {}
+            }
+        }
+        if let (false, Some(index)) = (ddl, indexes.first()) {
+            return Err(Error::new(
+                index.span,
+                "#[ormx(index)] has no effect without #[ormx(ddl)]",
+            ));
+        }
+        for index in &indexes {
+            for column in &index.columns {
+                if !fields.iter().any(|field| &field.field == column) {
+                    return Err(Error::new(
+                        column.span(),
+                        "index column does not refer to a field of the struct",
+                    ));
+                }
+            }
+        }
+        indexes.extend(
+            fields
+                .iter()
+                .filter(|field| field.unique)
+                .map(|field| Index {
+                    span: field.field.span(),
+                    name: None,
+                    columns: vec![field.field.clone()],
+                    unique: true,
+                    method: None,
+                    concurrently: false,
+                    fulltext: false,
+                    spatial: false,
+                }),
+        );
+
         Ok(Table {
             ident: value.ident.clone(),
             vis: value.vis.clone(),
-            table: table.ok_or_else(|| missing_attr("table"))?,
+            table,
+            schema,
             id,
             insertable,
             fields,
+            ddl,
+            indexes,
+            comment: doc_comment(&value.attrs),
+            skipped,
+            rename_all,
         })
     }
 }
+
+impl<B: Backend> Table<B> {
+    /// Replaces the fields annotated with `#[ormx(flatten)]` by the columns of their embeddable
+    /// structs, given the fields of the structs in the order of the flattened fields.
+    pub(super) fn flatten(&mut self, embeddables: Vec<FieldsNamed>) -> Result<()> {
+        let mut embeddables = embeddables.into_iter();
+        let mut fields = vec![];
+        for field in std::mem::take(&mut self.fields) {
+            let prefix = match &field.flatten {
+                Some(prefix) => prefix,
+                None => {
+                    fields.push(field);
+                    continue;
+                }
+            };
+            if field.field == self.id.field {
+                return Err(Error::new(field.field.span(), "the id can't be flattened"));
+            }
+            let mut indexed = self.indexes.iter().flat_map(|index| &index.columns);
+            if let Some(column) = indexed.find(|column| **column == field.field) {
+                return Err(Error::new(
+                    column.span(),
+                    "flattened fields can't be indexed",
+                ));
+            }
+            let embeddable = embeddables
+                .next()
+                .expect("embeddable struct has not been resolved");
+            if embeddable.named.is_empty() {
+                return Err(Error::new(
+                    field.field.span(),
+                    "the embeddable struct has no fields",
+                ));
+            }
+            for inner in embeddable.named.iter() {
+                let embedded = Embedded::new(&field.field, &field.ty, inner)?;
+                let mut column = TableField::<B>::try_from(inner)?;
+                column.column_name = format!("{}{}", prefix, column.column_name);
+                if let (Some(rename_all), false) = (self.rename_all, column.explicit_column) {
+                    column.column_name = rename_all.apply(&column.column_name);
+                    column.renamed = true;
+                }
+                column.field = embedded.ident();
+                column.ty = embedded.column_ty();
+                column.embedded = Some(embedded);
+                fields.push(column);
+            }
+        }
+        self.fields = fields;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proc_macro2::Span;
+    use syn::LitStr;
+
+    use super::parse_references;
+    use crate::table::ForeignKey;
+
+    fn parse(references: &str) -> syn::Result<ForeignKey> {
+        parse_references(&LitStr::new(references, Span::call_site()), None, None)
+    }
+
+    #[test]
+    fn parse_valid_references() {
+        let references = parse("users(id)").unwrap();
+        assert_eq!((&*references.table, &*references.column), ("users", "id"));
+        let references = parse(" auth.users ( user_id ) ").unwrap();
+        assert_eq!(
+            (&*references.table, &*references.column),
+            ("auth.users", "user_id")
+        );
+    }
+
+    #[test]
+    fn reject_invalid_references() {
+        for references in ["users", "users(id", "(id)", "users()", ""] {
+            assert!(parse(references).is_err(), "{}", references);
+        }
+    }
+}
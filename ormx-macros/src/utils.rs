@@ -1,6 +1,7 @@
 use proc_macro2::{Span, TokenStream};
 use quote::quote;
-use syn::{Error, Result};
+use syn::ext::IdentExt;
+use syn::{Attribute, Error, GenericArgument, Ident, Lit, Meta, PathArguments, Result, Type};
 
 pub fn box_future() -> TokenStream {
     quote!(ormx::exports::futures::future::BoxFuture)
@@ -23,3 +24,158 @@ pub fn missing_attr(attr: &str) -> Error {
         format!(r#"missing #[ormx({})] attribute"#, attr),
     )
 }
+
+/// Returns the schema and the name of a table. Unless the schema is given, a name of the form
+/// `<schema>.<table>` is split at the first dot which is not within a quoted identifier.
+pub fn split_schema(schema: Option<String>, table: String) -> (Option<String>, String) {
+    if schema.is_some() {
+        return (schema, table);
+    }
+    let mut quote = None;
+    let dot = table.char_indices().find(|&(_, c)| match quote {
+        Some(q) => {
+            if c == q {
+                quote = None;
+            }
+            false
+        }
+        None if c == '"' || c == '`' => {
+            quote = Some(c);
+            false
+        }
+        None => c == '.',
+    });
+    match dot {
+        Some((idx, _)) => (Some(table[..idx].to_owned()), table[idx + 1..].to_owned()),
+        None => (None, table),
+    }
+}
+
+/// Returns the doc comment of an item, with the lines joined by newlines.
+pub fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let lines = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::NameValue(meta)) => match meta.lit {
+                Lit::Str(doc) => Some(doc.value()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .map(|line| line.strip_prefix(' ').map(str::to_owned).unwrap_or(line))
+        .collect::<Vec<_>>();
+    let doc = lines.join("\n").trim().to_owned();
+    match doc.is_empty() {
+        true => None,
+        false => Some(doc),
+    }
+}
+
+/// Returns `T` if `ty` is an `Option<T>`.
+pub fn option_inner(ty: &Type) -> Option<&Type> {
+    let segment = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => match args.args.first()? {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// With the `runtime-queries` feature, queries are only checked when they are executed, so no
+/// database is required at compile time.
+pub const RUNTIME_QUERIES: bool = cfg!(feature = "runtime-queries");
+
+/// Generates `sqlx::query!(sql, args..)`, or `sqlx::query(sql).bind(..)` with `runtime-queries`.
+/// Like the macro, the runtime query binds references to the arguments.
+pub fn query(sql: &str, args: &[TokenStream]) -> TokenStream {
+    match RUNTIME_QUERIES {
+        true => quote!(sqlx::query::<ormx::Db>(#sql) #( .bind(&(#args)) )*),
+        false => quote!(sqlx::query!(#sql, #( #args ),*)),
+    }
+}
+
+/// Generates `sqlx::query_as!(out, sql, args..)`, or `sqlx::query_as(sql).bind(..)` with
+/// `runtime-queries`, which requires `out` to implement `FromRow`.
+pub fn query_as(out: &TokenStream, sql: &str, args: &[TokenStream]) -> TokenStream {
+    match RUNTIME_QUERIES {
+        true => quote!(sqlx::query_as::<ormx::Db, #out>(#sql) #( .bind(&(#args)) )*),
+        false => quote!(sqlx::query_as!(#out, #sql, #( #args ),*)),
+    }
+}
+
+/// Passes an argument of a custom type to a query. The macros need a type override, while the
+/// runtime queries take the value as it is.
+pub fn custom_type_arg(arg: TokenStream, ty: &Type) -> TokenStream {
+    match RUNTIME_QUERIES {
+        true => arg,
+        false => quote!(#arg as #ty),
+    }
+}
+
+/// Passes `arg`, a field of type `ty` annotated with `#[ormx(json)]`, as an argument of a query,
+/// wrapping it in `sqlx::types::Json`. Like custom types, the macros need a type override.
+pub fn json_arg(arg: TokenStream, ty: &Type) -> TokenStream {
+    match option_inner(ty) {
+        Some(ty) => custom_type_arg(
+            quote!(#arg.as_ref().map(sqlx::types::Json)),
+            &syn::parse2(quote!(Option<sqlx::types::Json<&#ty>>)).unwrap(),
+        ),
+        None => custom_type_arg(
+            quote!(sqlx::types::Json(&#arg)),
+            &syn::parse2(quote!(sqlx::types::Json<&#ty>)).unwrap(),
+        ),
+    }
+}
+
+/// Reads the column `field` of a row returned by `query`. The macros generate a record with one
+/// field per column, while the runtime queries return the row itself.
+pub fn record_field(record: TokenStream, field: &Ident, ty: &TokenStream) -> TokenStream {
+    match RUNTIME_QUERIES {
+        true => {
+            let name = field.unraw().to_string();
+            quote!(sqlx::Row::try_get::<#ty, _>(&#record, #name)?)
+        }
+        false => quote!(#record.#field),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_schema;
+
+    fn split(table: &str) -> (Option<String>, String) {
+        split_schema(None, table.to_owned())
+    }
+
+    #[test]
+    fn split_qualified_names() {
+        assert_eq!(split("users"), (None, "users".to_owned()));
+        assert_eq!(
+            split("auth.users"),
+            (Some("auth".to_owned()), "users".to_owned())
+        );
+        assert_eq!(
+            split("\"my.schema\".\"my.table\""),
+            (Some("\"my.schema\"".to_owned()), "\"my.table\"".to_owned())
+        );
+        assert_eq!(split("`my.table`"), (None, "`my.table`".to_owned()));
+        assert_eq!(split("\"a\"\".b\""), (None, "\"a\"\".b\"".to_owned()));
+    }
+
+    #[test]
+    fn keep_explicit_schema() {
+        assert_eq!(
+            split_schema(Some("auth".to_owned()), "a.b".to_owned()),
+            (Some("auth".to_owned()), "a.b".to_owned())
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Reading the schema of a table from the database, used by [verify_schema](crate::verify_schema)
+//! and [alter_sync](crate::alter_sync).
+
+use sqlx::{Database, Result, Row};
+
+use crate::{Db, IndexMeta};
+
+/// A column of a table in the database.
+pub(crate) struct Column {
+    pub name: String,
+    /// The type of the column, as reported by the database, e.g. `character varying(128)`.
+    pub sql_type: String,
+    pub nullable: bool,
+    pub has_default: bool,
+    /// Whether the column is an identity column, or on mysql `AUTO_INCREMENT`.
+    pub auto_increment: bool,
+    /// The default value of the column, e.g. `0`.
+    #[cfg(feature = "postgres")]
+    pub default: Option<String>,
+    pub comment: Option<String>,
+}
+
+/// An index of a table in the database.
+pub(crate) struct Index {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub unique: bool,
+    pub primary: bool,
+    /// Whether the index is required by a foreign key, so it can't be dropped.
+    pub foreign_key: bool,
+    /// The definition of the constraint backed by the index, e.g. `UNIQUE (email)`.
+    #[cfg(feature = "postgres")]
+    pub constraint: Option<String>,
+    /// The statement creating the index.
+    #[cfg(feature = "postgres")]
+    pub definition: String,
+}
+
+impl Index {
+    /// Returns true if this index covers `index`, i.e. has the same columns and is unique if
+    /// `index` is.
+    pub fn covers(&self, index: &IndexMeta) -> bool {
+        (self.unique || !index.unique)
+            && self.columns.len() == index.columns.len()
+            && (self.columns.iter().zip(index.columns))
+                .all(|(actual, expected)| same_name(actual, expected))
+    }
+}
+
+/// Returns true if `actual`, a name reported by the database, is `name` as stored by the database,
+/// e.g. [ColumnMeta::name](crate::ColumnMeta::name).
+#[cfg(feature = "postgres")]
+pub(crate) fn same_name(actual: &str, name: &str) -> bool {
+    actual == name
+}
+
+/// Returns true if `actual`, a name reported by the database, is `name`. Like mysql does, the
+/// names of columns are compared ignoring their case.
+#[cfg(feature = "mysql")]
+pub(crate) fn same_name(actual: &str, name: &str) -> bool {
+    actual.eq_ignore_ascii_case(name)
+}
+
+#[cfg(feature = "postgres")]
+pub(crate) async fn columns(
+    conn: &mut <Db as Database>::Connection,
+    schema: Option<&str>,
+    table: &str,
+) -> Result<Vec<Column>> {
+    let rows = sqlx::query(
+        "SELECT column_name::text, \
+         (SELECT format_type(a.atttypid, a.atttypmod) FROM pg_attribute a \
+          WHERE a.attrelid = format('%I.%I', table_schema, table_name)::regclass \
+          AND a.attname = column_name), \
+         is_nullable::text = 'YES', \
+         column_default IS NOT NULL OR is_identity::text = 'YES' OR is_generated::text = 'ALWAYS', \
+         column_default::text, \
+         is_identity::text = 'YES', \
+         col_description(format('%I.%I', table_schema, table_name)::regclass, ordinal_position) \
+         FROM information_schema.columns \
+         WHERE table_schema = COALESCE($2, current_schema()) \
+         AND table_name = $1 \
+         ORDER BY ordinal_position",
+    )
+    .bind(table)
+    .bind(schema)
+    .fetch_all(conn)
+    .await?;
+    rows.iter()
+        .map(|row| {
+            Ok(Column {
+                name: row.try_get(0)?,
+                sql_type: row.try_get(1)?,
+                nullable: row.try_get(2)?,
+                has_default: row.try_get(3)?,
+                default: row.try_get(4)?,
+                auto_increment: row.try_get(5)?,
+                comment: row.try_get(6)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "postgres")]
+pub(crate) async fn indexes(
+    conn: &mut <Db as Database>::Connection,
+    schema: Option<&str>,
+    table: &str,
+) -> Result<Vec<Index>> {
+    let rows = sqlx::query(
+        "SELECT i.relname::text, ix.indisunique, ix.indisprimary, \
+         array_agg(a.attname::text ORDER BY k.n), pg_get_indexdef(ix.indexrelid), \
+         (SELECT pg_get_constraintdef(c.oid) FROM pg_constraint c \
+          WHERE c.conindid = ix.indexrelid AND c.conrelid = t.oid \
+          AND c.contype IN ('p', 'u', 'x') LIMIT 1) \
+         FROM pg_index ix \
+         JOIN pg_class i ON i.oid = ix.indexrelid \
+         JOIN pg_class t ON t.oid = ix.indrelid \
+         JOIN pg_namespace ns ON ns.oid = t.relnamespace \
+         CROSS JOIN LATERAL unnest(ix.indkey) WITH ORDINALITY AS k(attnum, n) \
+         JOIN pg_attribute a ON a.attrelid = t.oid AND a.attnum = k.attnum \
+         WHERE ns.nspname = COALESCE($2, current_schema()) AND t.relname = $1 \
+         GROUP BY ix.indexrelid, i.relname, ix.indisunique, ix.indisprimary, t.oid \
+         ORDER BY i.relname",
+    )
+    .bind(table)
+    .bind(schema)
+    .fetch_all(conn)
+    .await?;
+    rows.iter()
+        .map(|row| {
+            Ok(Index {
+                name: row.try_get(0)?,
+                unique: row.try_get(1)?,
+                primary: row.try_get(2)?,
+                columns: row.try_get(3)?,
+                foreign_key: false,
+                definition: row.try_get(4)?,
+                constraint: row.try_get(5)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "mysql")]
+pub(crate) async fn columns(
+    conn: &mut <Db as Database>::Connection,
+    schema: Option<&str>,
+    table: &str,
+) -> Result<Vec<Column>> {
+    let rows = sqlx::query(
+        "SELECT CAST(column_name AS CHAR), CAST(column_type AS CHAR), \
+         CAST(is_nullable = 'YES' AS SIGNED), \
+         CAST(column_default IS NOT NULL OR extra LIKE '%auto_increment%' \
+         OR extra LIKE '%GENERATED%' AS SIGNED), \
+         CAST(extra LIKE '%auto_increment%' AS SIGNED), \
+         CAST(NULLIF(column_comment, '') AS CHAR) \
+         FROM information_schema.columns \
+         WHERE table_schema = COALESCE(?, DATABASE()) AND table_name = ? \
+         ORDER BY ordinal_position",
+    )
+    .bind(schema)
+    .bind(table)
+    .fetch_all(conn)
+    .await?;
+    rows.iter()
+        .map(|row| {
+            Ok(Column {
+                name: row.try_get(0)?,
+                sql_type: row.try_get(1)?,
+                nullable: row.try_get::<i64, _>(2)? != 0,
+                has_default: row.try_get::<i64, _>(3)? != 0,
+                auto_increment: row.try_get::<i64, _>(4)? != 0,
+                comment: row.try_get(5)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "mysql")]
+pub(crate) async fn indexes(
+    conn: &mut <Db as Database>::Connection,
+    schema: Option<&str>,
+    table: &str,
+) -> Result<Vec<Index>> {
+    let rows = sqlx::query(
+        "SELECT CAST(s.index_name AS CHAR), CAST(s.non_unique AS SIGNED), \
+         CAST(s.column_name AS CHAR), \
+         CAST(EXISTS (SELECT * FROM information_schema.table_constraints c \
+          WHERE c.table_schema = s.table_schema AND c.table_name = s.table_name \
+          AND c.constraint_name = s.index_name AND c.constraint_type = 'FOREIGN KEY') AS SIGNED) \
+         FROM information_schema.statistics s \
+         WHERE s.table_schema = COALESCE(?, DATABASE()) AND s.table_name = ? \
+         ORDER BY s.index_name, s.seq_in_index",
+    )
+    .bind(schema)
+    .bind(table)
+    .fetch_all(conn)
+    .await?;
+    let mut indexes: Vec<Index> = vec![];
+    for row in rows {
+        let name: String = row.try_get(0)?;
+        let column: String = row.try_get(2)?;
+        match indexes.last_mut() {
+            Some(index) if index.name == name => index.columns.push(column),
+            _ => indexes.push(Index {
+                primary: name == "PRIMARY",
+                name,
+                columns: vec![column],
+                unique: row.try_get::<i64, _>(1)? == 0,
+                foreign_key: row.try_get::<i64, _>(3)? != 0,
+            }),
+        }
+    }
+    Ok(indexes)
+}
@@ -28,22 +28,47 @@
 //! ```
 //!
 //! # Documentation
-//! See the docs of [derive(Table)](derive.Table.html) and [Patch](trait.Patch.html).
+//! See the docs of [derive(Table)](derive.Table.html), [Patch](trait.Patch.html) and
+//! [TableMeta](trait.TableMeta.html).
+//!
+//! # Features
+//! By default, every generated query is checked against the database at compile time, which
+//! requires `DATABASE_URL` or prepared sqlx offline data. With the `runtime-queries` feature,
+//! queries are only checked when they are executed, so no database is required to build.
 
 use futures::future::BoxFuture;
 use futures::stream::BoxStream;
-use sqlx::{Database, Executor, Result};
+use sqlx::{Acquire, Database, Executor, Result};
 
+pub use meta::*;
+#[cfg(any(feature = "mysql", feature = "postgres"))]
+pub use migrate::*;
 pub use ormx_macros::*;
+#[cfg(any(feature = "mysql", feature = "postgres"))]
+pub use query2::ConditionalQuery;
+pub use schema::*;
+#[cfg(any(feature = "mysql", feature = "postgres"))]
+pub use verify::*;
 
 #[doc(hidden)]
 pub mod exports {
-    pub use crate::query2::map::*;
+    #[cfg(any(feature = "mysql", feature = "postgres"))]
+    pub use crate::query2::{bind, bind_list, dummy, null, null_list, same_output, QueryBuilder};
     pub use futures;
+    #[cfg(feature = "json")]
+    pub use serde;
 }
 
+#[cfg(any(feature = "mysql", feature = "postgres"))]
+mod introspect;
+mod meta;
+#[cfg(any(feature = "mysql", feature = "postgres"))]
+mod migrate;
 #[cfg(any(feature = "mysql", feature = "postgres"))]
 mod query2;
+mod schema;
+#[cfg(any(feature = "mysql", feature = "postgres"))]
+mod verify;
 
 #[cfg(feature = "mysql")]
 pub type Db = sqlx::MySql;
@@ -67,7 +92,7 @@ where
     fn insert(
         db: &mut <Db as Database>::Connection,
         row: impl Insert<Table = Self>,
-    ) -> BoxFuture<Result<Self>> {
+    ) -> BoxFuture<'_, Result<Self>> {
         row.insert(db)
     }
 
@@ -129,6 +154,22 @@ where
         db: impl Executor<'c, Database = Db> + 'a,
     ) -> BoxFuture<'a, Result<()>>;
 
+    /// Like [Table::update], but also refreshes the fields annotated with `#[ormx(read_only)]`,
+    /// which may be changed by the database when the row is updated.
+    /// On mysql, this requires a second query, so it takes a connection which can be acquired.
+    ///
+    /// By default, the row is updated and reloaded afterwards.
+    fn update_and_refresh<'a, 'c: 'a>(
+        &'a mut self,
+        db: impl Acquire<'c, Database = Db> + Send + 'a,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut conn = db.acquire().await?;
+            self.update(&mut *conn).await?;
+            self.reload(&mut *conn).await
+        })
+    }
+
     // Refresh this row, querying all columns from the database.
     fn reload<'a, 'c: 'a>(
         &'a mut self,
@@ -182,5 +223,20 @@ where
     type Table: Table;
 
     /// Insert a row into the database, returning the inserted row.
-    fn insert(self, db: &mut <Db as Database>::Connection) -> BoxFuture<Result<Self::Table>>;
+    fn insert(self, db: &mut <Db as Database>::Connection) -> BoxFuture<'_, Result<Self::Table>>;
+}
+
+/// A struct whose fields are mapped to a group of columns, which can be flattened into tables
+/// and patches using `#[ormx(flatten)]`.
+/// This trait can be derived using [derive(Embeddable)](derive.Embeddable.html).
+pub trait Embeddable {
+    /// The columns of the fields, without the prefix of the flattened field.
+    const COLUMNS: &'static [&'static str];
+}
+
+/// An enum choosing the order of the rows returned by a conditional query.
+/// This trait can be derived using [derive(OrderBy)](derive.OrderBy.html).
+pub trait OrderBy {
+    /// Returns the SQL following `ORDER BY` for this variant.
+    fn order_by(&self) -> &'static str;
 }
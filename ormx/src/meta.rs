@@ -0,0 +1,155 @@
+use std::fmt::{self, Debug, Formatter};
+use std::marker::PhantomData;
+
+/// Static information about a table, implemented by `derive(Table)`.
+///
+/// This allows inspecting a table at runtime without duplicating its table and column names.
+pub trait TableMeta {
+    /// Name of the table, as stored by the database. On postgres, names which are not quoted are
+    /// folded to lowercase.
+    const TABLE: &'static str;
+    /// Schema containing the table, if declared using `#[ormx(schema = ..)]`. Otherwise, the table
+    /// is looked up in the current schema.
+    const SCHEMA: Option<&'static str>;
+    /// Name of the table as used within queries, qualified by its schema and quoted if required,
+    /// e.g. `auth."user"`.
+    const QUALIFIED_TABLE: &'static str;
+    /// Name of the ID column.
+    const ID_COLUMN: &'static str;
+    /// All columns of the table, in the order in which the fields are declared.
+    const COLUMNS: &'static [ColumnMeta];
+    /// The list of columns selected by the generated queries, e.g. `id AS user_id, email`.
+    ///
+    /// To use this list in a query checked at compile time, use `@columns(Table)` within
+    /// [conditional_query_as!](macro.conditional_query_as.html).
+    const SELECT_COLUMNS: &'static str;
+    /// All indexes of the table, if it is annotated with `#[ormx(ddl)]`.
+    const INDEXES: &'static [IndexMeta];
+
+    /// Returns the column with the given name, if it exists.
+    fn column(name: &str) -> Option<&'static ColumnMeta> {
+        Self::COLUMNS.iter().find(|column| column.name == name)
+    }
+}
+
+/// Information about a single column of a table.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ColumnMeta {
+    /// Name of the column, as stored by the database.
+    pub name: &'static str,
+    /// Name of the field the column is mapped to.
+    pub field: &'static str,
+    /// Whether the column may be NULL, i.e. the field is an `Option`.
+    pub nullable: bool,
+    /// Whether the value of the column is generated by the database (`#[ormx(default)]`).
+    pub default: bool,
+    /// Whether the column is never written (`#[ormx(read_only)]`).
+    pub read_only: bool,
+    /// Whether the field is annotated with `#[ormx(custom_type)]`.
+    pub custom_type: bool,
+    /// The type of the column, if the table is annotated with `#[ormx(ddl)]`.
+    pub sql_type: Option<&'static str>,
+    /// The default value of the column (`#[ormx(default = ..)]`).
+    pub default_expr: Option<&'static str>,
+    /// Whether the column is part of the primary key.
+    pub primary_key: bool,
+    /// Whether the values of the column are generated by the database (`#[ormx(auto_increment)]`
+    /// or `#[ormx(identity = ..)]`).
+    pub auto_increment: bool,
+    /// The column referenced by this column (`#[ormx(references = ..)]`).
+    pub references: Option<ForeignKeyMeta>,
+    /// The previous name of the column (`#[ormx(renamed_from = ..)]`), used when generating
+    /// migrations.
+    pub renamed_from: Option<&'static str>,
+    /// The doc comment of the field, used as the comment of the column.
+    pub comment: Option<&'static str>,
+}
+
+/// A foreign key, referencing a column of another table.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ForeignKeyMeta {
+    /// Name of the referenced table.
+    pub table: &'static str,
+    /// Name of the referenced column.
+    pub column: &'static str,
+    /// What happens to this row when the referenced row is deleted.
+    pub on_delete: ReferentialAction,
+    /// What happens to this row when the referenced column is updated.
+    pub on_update: ReferentialAction,
+}
+
+/// The action taken when a referenced row is deleted or updated.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ReferentialAction {
+    Cascade,
+    Restrict,
+    SetNull,
+    SetDefault,
+    NoAction,
+}
+
+impl ReferentialAction {
+    /// Returns the SQL of this action, e.g. `SET NULL`.
+    pub const fn sql(self) -> &'static str {
+        match self {
+            ReferentialAction::Cascade => "CASCADE",
+            ReferentialAction::Restrict => "RESTRICT",
+            ReferentialAction::SetNull => "SET NULL",
+            ReferentialAction::SetDefault => "SET DEFAULT",
+            ReferentialAction::NoAction => "NO ACTION",
+        }
+    }
+}
+
+/// Information about an index of a table.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct IndexMeta {
+    /// Name of the index in the database.
+    pub name: &'static str,
+    /// Names of the indexed columns.
+    pub columns: &'static [&'static str],
+    /// Whether the index is unique.
+    pub unique: bool,
+}
+
+/// A typed marker for a column of the table `T`, which contains values of type `V`.
+///
+/// `derive(Table)` generates an associated constant for every field, e.g. `User::EMAIL`.
+pub struct Column<T, V> {
+    meta: &'static ColumnMeta,
+    _phantom: PhantomData<fn() -> (T, V)>,
+}
+
+impl<T, V> Column<T, V> {
+    #[doc(hidden)]
+    pub const fn new(meta: &'static ColumnMeta) -> Self {
+        Column {
+            meta,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns the metadata of this column.
+    pub const fn meta(&self) -> &'static ColumnMeta {
+        self.meta
+    }
+
+    /// Returns the name of this column in the database.
+    pub const fn name(&self) -> &'static str {
+        self.meta.name
+    }
+}
+
+impl<T, V> Clone for Column<T, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, V> Copy for Column<T, V> {}
+
+impl<T, V> Debug for Column<T, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Column").field(self.meta).finish()
+    }
+}
@@ -0,0 +1,653 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sqlx::{Acquire, Result};
+
+use crate::introspect::{columns, indexes, same_name, Column, Index};
+use crate::{ColumnMeta, Db, IndexMeta, Schema};
+
+/// A migration, consisting of the statements applying it and the statements reverting it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Migration {
+    /// Statements applying the migration, in the order in which they are executed.
+    pub up: Vec<String>,
+    /// Statements reverting the migration, in the order in which they are executed.
+    pub down: Vec<String>,
+}
+
+impl Migration {
+    /// Returns true if the migration contains no statements.
+    pub fn is_empty(&self) -> bool {
+        self.up.is_empty() && self.down.is_empty()
+    }
+
+    /// Appends `other` to this migration. When reverting, `other` is reverted first.
+    pub fn extend(&mut self, other: Migration) {
+        self.up.extend(other.up);
+        self.down.splice(0..0, other.down);
+    }
+
+    /// Appends a statement, together with the statement reverting it.
+    fn push(&mut self, up: String, down: String) {
+        self.up.push(up);
+        self.down.insert(0, down);
+    }
+
+    /// Writes this migration into `dir` as a reversible migration understood by `sqlx migrate`,
+    /// `<timestamp>_<name>.up.sql` and `<timestamp>_<name>.down.sql`.
+    ///
+    /// Returns the path of the `.up.sql` file, or `None` if the migration is empty and nothing
+    /// has been written.
+    pub fn write(&self, dir: impl AsRef<Path>, name: &str) -> io::Result<Option<PathBuf>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let prefix = format!("{}_{}", timestamp(SystemTime::now()), name);
+        let up = dir.join(format!("{}.up.sql", prefix));
+        fs::write(&up, script(&self.up))?;
+        fs::write(dir.join(format!("{}.down.sql", prefix)), script(&self.down))?;
+        Ok(Some(up))
+    }
+}
+
+/// Compares the table `T` with the table in the database, returning the migration which brings
+/// the database up to date.
+///
+/// The migration creates the table if it does not exist yet. Otherwise, it
+/// - adds new columns and drops columns which no longer exist in `T`
+/// - renames columns annotated with `#[ormx(renamed_from = "<old column>")]`, and columns whose
+///   names only differ in case
+/// - changes the types of columns, whether they may be NULL and their comments
+/// - drops indexes which are no longer declared, and creates new ones
+///
+/// Changes to default values, identity columns, foreign keys and the comment of the table are not
+/// detected, but [verify_schema](crate::verify_schema) reports columns which should be identity
+/// columns. Reverting a migration which dropped a column restores the column, but not its data,
+/// and on postgres not its comment.
+///
+/// The generated statements should be reviewed before running them:
+/// ```rust,ignore
+/// let mut migration = ormx::alter_sync::<User>(&pool).await?;
+/// migration.extend(ormx::alter_sync::<Post>(&pool).await?);
+/// migration.write("migrations", "sync")?;
+/// ```
+pub async fn alter_sync<'c, T: Schema>(db: impl Acquire<'c, Database = Db>) -> Result<Migration> {
+    let mut conn = db.acquire().await?;
+    let columns = columns(&mut conn, T::SCHEMA, T::TABLE).await?;
+    let indexes = indexes(&mut conn, T::SCHEMA, T::TABLE).await?;
+    if columns.is_empty() {
+        return Ok(force_sync::<T>());
+    }
+
+    let table = T::QUALIFIED_TABLE;
+    let mut migration = Migration::default();
+
+    // indexes on renamed or dropped columns are dropped before the columns change
+    let (kept, stale): (Vec<&Index>, Vec<&Index>) = indexes
+        .iter()
+        .filter(|index| !index.primary && !index.foreign_key)
+        .partition(|index| T::INDEXES.iter().any(|meta| matches(index, meta)));
+    for index in stale {
+        migration.push(
+            drop_index_sql(table, T::SCHEMA, index),
+            create_index_sql(table, index),
+        );
+    }
+
+    let find = |name: &str| columns.iter().find(|c| same_name(&c.name, name));
+    // e.g. columns which were not quoted before, so their names were folded to lowercase
+    let find_case = |name: &str| {
+        columns
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name) && T::column(&c.name).is_none())
+    };
+    let mut existing = vec![];
+    for (meta, definition) in T::COLUMNS.iter().zip(T::column_definitions()) {
+        let actual = find(meta.name)
+            .or_else(|| meta.renamed_from.and_then(find))
+            .or_else(|| find_case(meta.name));
+        let actual = match actual {
+            Some(actual) => actual,
+            None => {
+                add_column(&mut migration, table, meta, &definition);
+                continue;
+            }
+        };
+        existing.push(actual.name.as_str());
+        if !same_name(&actual.name, meta.name) {
+            migration.push(
+                format!(
+                    "ALTER TABLE {} RENAME COLUMN {} TO {}",
+                    table,
+                    quote(&actual.name),
+                    quote(meta.name)
+                ),
+                format!(
+                    "ALTER TABLE {} RENAME COLUMN {} TO {}",
+                    table,
+                    quote(meta.name),
+                    quote(&actual.name)
+                ),
+            );
+        }
+        alter_column(&mut migration, table, meta, &definition, actual);
+    }
+    for actual in &columns {
+        if !existing.contains(&actual.name.as_str()) {
+            migration.push(
+                format!("ALTER TABLE {} DROP COLUMN {}", table, quote(&actual.name)),
+                format!(
+                    "ALTER TABLE {} ADD COLUMN {}",
+                    table,
+                    existing_definition(actual)
+                ),
+            );
+        }
+    }
+
+    for (meta, sql) in T::INDEXES.iter().zip(T::add_indexes_sql()) {
+        if !kept.iter().any(|index| matches(index, meta)) {
+            migration.push(sql, drop_index_meta_sql(table, T::SCHEMA, meta));
+        }
+    }
+
+    Ok(migration)
+}
+
+/// Returns the migration dropping the table `T`, if it exists, and creating it again.
+/// All data in the table is lost, and reverting the migration drops the table.
+pub fn force_sync<T: Schema>() -> Migration {
+    let mut up = vec![format!("DROP TABLE IF EXISTS {}", T::QUALIFIED_TABLE)];
+    up.push(T::create_table_sql());
+    up.extend(T::create_indexes_sql());
+    up.extend(T::create_comments_sql());
+    Migration {
+        up,
+        down: vec![format!("DROP TABLE IF EXISTS {}", T::QUALIFIED_TABLE)],
+    }
+}
+
+/// Returns true if `index` exists in the database exactly as declared.
+fn matches(index: &Index, meta: &IndexMeta) -> bool {
+    index.unique == meta.unique && index.covers(meta)
+}
+
+/// Renders the statements of a migration, one per line.
+fn script(statements: &[String]) -> String {
+    statements
+        .iter()
+        .map(|statement| format!("{};\n", statement))
+        .collect()
+}
+
+/// Formats `time` like `sqlx migrate add`, e.g. `20210217160954`.
+fn timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // converts days since 1970-01-01 into a date of the proleptic gregorian calendar
+    // (http://howardhinnant.github.io/date_algorithms.html#civil_from_days)
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as u64;
+
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[cfg(feature = "postgres")]
+fn quote(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+#[cfg(feature = "mysql")]
+fn quote(ident: &str) -> String {
+    format!("`{}`", ident.replace('`', "``"))
+}
+
+/// Renders a string literal.
+fn string_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Adds a column and sets its comment.
+#[cfg(feature = "postgres")]
+fn add_column(migration: &mut Migration, table: &str, meta: &ColumnMeta, definition: &str) {
+    migration.push(
+        format!("ALTER TABLE {} ADD COLUMN {}", table, definition),
+        format!("ALTER TABLE {} DROP COLUMN {}", table, quote(meta.name)),
+    );
+    alter_comment(migration, table, meta, None);
+}
+
+/// Adds a column, including its comment.
+#[cfg(feature = "mysql")]
+fn add_column(migration: &mut Migration, table: &str, meta: &ColumnMeta, definition: &str) {
+    migration.push(
+        format!("ALTER TABLE {} ADD COLUMN {}", table, definition),
+        format!("ALTER TABLE {} DROP COLUMN {}", table, quote(meta.name)),
+    );
+}
+
+/// Changes the comment of a column, if it differs from `actual`.
+#[cfg(feature = "postgres")]
+fn alter_comment(migration: &mut Migration, table: &str, meta: &ColumnMeta, actual: Option<&str>) {
+    if meta.comment != actual {
+        let comment = |comment: Option<&str>| {
+            format!(
+                "COMMENT ON COLUMN {}.{} IS {}",
+                table,
+                quote(meta.name),
+                comment.map_or_else(|| "NULL".to_owned(), string_literal)
+            )
+        };
+        migration.push(comment(meta.comment), comment(actual));
+    }
+}
+
+/// Renders the definition of a column in the database, e.g. `"extra" integer NOT NULL DEFAULT 0`.
+#[cfg(feature = "postgres")]
+fn existing_definition(column: &Column) -> String {
+    let mut definition = format!("{} {}", quote(&column.name), column.sql_type);
+    if !column.nullable {
+        definition.push_str(" NOT NULL");
+    }
+    if let Some(default) = &column.default {
+        definition.push_str(&format!(" DEFAULT {}", default));
+    }
+    definition
+}
+
+/// Renders the definition of a column in the database, e.g. `` `extra` int NOT NULL ``.
+/// The default value is not included, since mysql does not report it as an expression.
+#[cfg(feature = "mysql")]
+fn existing_definition(column: &Column) -> String {
+    format!("{} {}", quote(&column.name), existing_type(column))
+}
+
+/// Renders the type of a column in the database, whether it may be NULL and its comment, e.g.
+/// `int NOT NULL`.
+#[cfg(feature = "mysql")]
+fn existing_type(column: &Column) -> String {
+    let mut definition = column.sql_type.clone();
+    if !column.nullable {
+        definition.push_str(" NOT NULL");
+    }
+    if let Some(comment) = &column.comment {
+        definition.push_str(&format!(" COMMENT {}", string_literal(comment)));
+    }
+    definition
+}
+
+/// Changes the type of a column, whether it may be NULL and its comment, if they differ from the
+/// database.
+#[cfg(feature = "postgres")]
+fn alter_column(
+    migration: &mut Migration,
+    table: &str,
+    meta: &ColumnMeta,
+    _definition: &str,
+    actual: &Column,
+) {
+    let sql_type = meta.sql_type.unwrap_or_default();
+    if normalize_type(sql_type) != normalize_type(&actual.sql_type) {
+        let column = quote(meta.name);
+        let alter = |sql_type: &str| {
+            format!(
+                "ALTER TABLE {} ALTER COLUMN {} TYPE {} USING {}::{}",
+                table, column, sql_type, column, sql_type
+            )
+        };
+        migration.push(alter(sql_type), alter(&actual.sql_type));
+    }
+    if meta.nullable != actual.nullable {
+        let alter = |nullable: bool| {
+            format!(
+                "ALTER TABLE {} ALTER COLUMN {} {} NOT NULL",
+                table,
+                quote(meta.name),
+                if nullable { "DROP" } else { "SET" }
+            )
+        };
+        migration.push(alter(meta.nullable), alter(actual.nullable));
+    }
+    alter_comment(migration, table, meta, actual.comment.as_deref());
+}
+
+/// Changes the type of a column, whether it may be NULL and its comment, if they differ from the
+/// database. Since mysql redefines the whole column, its default value is not restored when
+/// reverting.
+#[cfg(feature = "mysql")]
+fn alter_column(
+    migration: &mut Migration,
+    table: &str,
+    meta: &ColumnMeta,
+    definition: &str,
+    actual: &Column,
+) {
+    let sql_type = meta.sql_type.unwrap_or_default();
+    if normalize_type(sql_type) != normalize_type(&actual.sql_type)
+        || meta.nullable != actual.nullable
+        || meta.comment != actual.comment.as_deref()
+    {
+        migration.push(
+            format!("ALTER TABLE {} MODIFY COLUMN {}", table, definition),
+            format!(
+                "ALTER TABLE {} MODIFY COLUMN {} {}",
+                table,
+                quote(meta.name),
+                existing_type(actual)
+            ),
+        );
+    }
+}
+
+/// Normalizes a type, so that aliases like `VARCHAR(128)` and `character varying(128)` compare
+/// equal.
+#[cfg(feature = "postgres")]
+pub(crate) fn normalize_type(sql_type: &str) -> String {
+    let sql_type = sql_type
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+        .replace(", ", ",");
+    // e.g. `timestamp`, `(3)` and `with time zone`
+    let (name, args, rest) = match sql_type.find('(') {
+        Some(start) => {
+            let end = sql_type[start..]
+                .find(')')
+                .map_or(sql_type.len(), |end| start + end + 1);
+            (&sql_type[..start], &sql_type[start..end], &sql_type[end..])
+        }
+        None => (sql_type.as_str(), "", ""),
+    };
+    let name = match (name.trim_end(), rest) {
+        ("int" | "int4" | "serial", _) => "integer",
+        ("int2" | "smallserial", _) => "smallint",
+        ("int8" | "bigserial", _) => "bigint",
+        ("float4", _) => "real",
+        ("float8", _) => "double precision",
+        ("bool", _) => "boolean",
+        ("varchar", _) => "character varying",
+        ("char", _) => "character",
+        ("decimal", _) => "numeric",
+        ("timestamp", "") => return format!("timestamp{} without time zone", args),
+        ("timestamptz", _) => return format!("timestamp{} with time zone", args),
+        ("time", "") => return format!("time{} without time zone", args),
+        ("timetz", _) => return format!("time{} with time zone", args),
+        (name, _) => name,
+    };
+    format!("{}{}{}", name, args, rest)
+}
+
+/// Normalizes a type, so that aliases like `INTEGER` and `int` compare equal.
+#[cfg(feature = "mysql")]
+pub(crate) fn normalize_type(sql_type: &str) -> String {
+    let sql_type = sql_type
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+        .replace(", ", ",");
+    let (name, rest) = sql_type.split_once(' ').unwrap_or((&sql_type, ""));
+    let (name, args) = match name.find('(') {
+        Some(idx) => name.split_at(idx),
+        None => (name, ""),
+    };
+    let (name, args) = match (name, args) {
+        ("bool" | "boolean", _) | ("tinyint", "(1)") => ("tinyint", "(1)"),
+        // integer display widths are deprecated and not reported by mysql 8
+        ("integer", _) => ("int", ""),
+        ("tinyint" | "smallint" | "mediumint" | "int" | "bigint", _) => (name, ""),
+        ("dec" | "numeric" | "fixed" | "decimal", "") => ("decimal", "(10,0)"),
+        ("dec" | "numeric" | "fixed", _) => ("decimal", args),
+        ("real", _) => ("double", args),
+        _ => (name, args),
+    };
+    let rest = rest.trim_start_matches("precision").trim();
+    match rest.is_empty() {
+        true => format!("{}{}", name, args),
+        false => format!("{}{} {}", name, args, rest),
+    }
+}
+
+#[cfg(feature = "postgres")]
+fn drop_index_sql(table: &str, schema: Option<&str>, index: &Index) -> String {
+    match index.constraint {
+        Some(_) => format!(
+            "ALTER TABLE {} DROP CONSTRAINT {}",
+            table,
+            quote(&index.name)
+        ),
+        None => format!("DROP INDEX {}", qualify(schema, &quote(&index.name))),
+    }
+}
+
+#[cfg(feature = "postgres")]
+fn create_index_sql(table: &str, index: &Index) -> String {
+    match &index.constraint {
+        Some(constraint) => format!(
+            "ALTER TABLE {} ADD CONSTRAINT {} {}",
+            table,
+            quote(&index.name),
+            constraint
+        ),
+        None => index.definition.clone(),
+    }
+}
+
+#[cfg(feature = "postgres")]
+fn drop_index_meta_sql(_table: &str, schema: Option<&str>, index: &IndexMeta) -> String {
+    format!("DROP INDEX {}", qualify(schema, &quote(index.name)))
+}
+
+/// Indexes are contained in the schema of their table, so they are qualified by it when dropped.
+#[cfg(feature = "postgres")]
+fn qualify(schema: Option<&str>, name: &str) -> String {
+    match schema {
+        Some(schema) => format!("{}.{}", quote(schema), name),
+        None => name.to_owned(),
+    }
+}
+
+#[cfg(feature = "mysql")]
+fn drop_index_sql(table: &str, _schema: Option<&str>, index: &Index) -> String {
+    format!("ALTER TABLE {} DROP INDEX {}", table, quote(&index.name))
+}
+
+#[cfg(feature = "mysql")]
+fn create_index_sql(table: &str, index: &Index) -> String {
+    format!(
+        "ALTER TABLE {} ADD {}INDEX {} ({})",
+        table,
+        if index.unique { "UNIQUE " } else { "" },
+        quote(&index.name),
+        index
+            .columns
+            .iter()
+            .map(|c| quote(c))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+#[cfg(feature = "mysql")]
+fn drop_index_meta_sql(table: &str, _schema: Option<&str>, index: &IndexMeta) -> String {
+    format!("ALTER TABLE {} DROP INDEX {}", table, quote(index.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{normalize_type, script, timestamp, Migration};
+
+    fn migration(up: &[&str], down: &[&str]) -> Migration {
+        Migration {
+            up: up.iter().map(|s| s.to_string()).collect(),
+            down: down.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn push_reverts_in_reverse_order() {
+        let mut m = Migration::default();
+        assert!(m.is_empty());
+        m.push("up 1".into(), "down 1".into());
+        m.push("up 2".into(), "down 2".into());
+        assert_eq!(m, migration(&["up 1", "up 2"], &["down 2", "down 1"]));
+    }
+
+    #[test]
+    fn extend_reverts_other_first() {
+        let mut m = migration(&["up 1", "up 2"], &["down 2", "down 1"]);
+        m.extend(migration(&["up 3", "up 4"], &["down 4", "down 3"]));
+        assert_eq!(
+            m,
+            migration(
+                &["up 1", "up 2", "up 3", "up 4"],
+                &["down 4", "down 3", "down 2", "down 1"]
+            )
+        );
+    }
+
+    #[test]
+    fn script_terminates_statements() {
+        assert_eq!(script(&[]), "");
+        assert_eq!(
+            script(&["DROP TABLE a".into(), "DROP TABLE b".into()]),
+            "DROP TABLE a;\nDROP TABLE b;\n"
+        );
+    }
+
+    #[test]
+    fn format_timestamps() {
+        for (secs, formatted) in [
+            (0, "19700101000000"),
+            (946684799, "19991231235959"),
+            (951868800, "20000301000000"),
+            (1583020799, "20200229235959"),
+            (1613578194, "20210217160954"),
+        ] {
+            assert_eq!(timestamp(UNIX_EPOCH + Duration::from_secs(secs)), formatted);
+        }
+        // times before the epoch are clamped to it
+        assert_eq!(
+            timestamp(UNIX_EPOCH - Duration::from_secs(1)),
+            "19700101000000"
+        );
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn normalize_aliases() {
+        for (declared, reported) in [
+            ("INTEGER", "integer"),
+            ("INT", "integer"),
+            ("SERIAL", "integer"),
+            ("BIGSERIAL", "bigint"),
+            ("INT8", "bigint"),
+            ("SMALLINT", "smallint"),
+            ("REAL", "real"),
+            ("DOUBLE PRECISION", "double precision"),
+            ("FLOAT8", "double precision"),
+            ("BOOL", "boolean"),
+            ("VARCHAR(128)", "character varying(128)"),
+            ("CHAR(2)", "character(2)"),
+            ("DECIMAL(10, 2)", "numeric(10,2)"),
+            ("TEXT", "text"),
+        ] {
+            assert_eq!(
+                normalize_type(declared),
+                normalize_type(reported),
+                "{}",
+                declared
+            );
+        }
+        assert_ne!(normalize_type("INTEGER"), normalize_type("bigint"));
+        assert_ne!(
+            normalize_type("VARCHAR(128)"),
+            normalize_type("character varying(64)")
+        );
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn normalize_timestamps() {
+        for (declared, reported) in [
+            ("TIMESTAMP", "timestamp without time zone"),
+            ("TIMESTAMP(3)", "timestamp(3) without time zone"),
+            ("TIMESTAMPTZ", "timestamp with time zone"),
+            ("TIMESTAMPTZ(3)", "timestamp(3) with time zone"),
+            ("timestamp(3) with time zone", "timestamp(3) with time zone"),
+            ("TIME", "time without time zone"),
+            ("TIMETZ", "time with time zone"),
+        ] {
+            assert_eq!(
+                normalize_type(declared),
+                normalize_type(reported),
+                "{}",
+                declared
+            );
+        }
+        assert_ne!(
+            normalize_type("TIMESTAMP"),
+            normalize_type("timestamp with time zone")
+        );
+    }
+
+    #[cfg(feature = "mysql")]
+    #[test]
+    fn normalize_aliases() {
+        for (declared, reported) in [
+            ("INTEGER", "int"),
+            ("INT", "int(11)"),
+            ("BIGINT UNSIGNED", "bigint(20) unsigned"),
+            ("BOOLEAN", "tinyint(1)"),
+            ("DECIMAL", "decimal(10,0)"),
+            ("NUMERIC(10, 2)", "decimal(10,2)"),
+            ("REAL", "double"),
+            ("DOUBLE PRECISION", "double"),
+            ("VARCHAR(255)", "varchar(255)"),
+        ] {
+            assert_eq!(
+                normalize_type(declared),
+                normalize_type(reported),
+                "{}",
+                declared
+            );
+        }
+        assert_ne!(normalize_type("TINYINT"), normalize_type("tinyint(1)"));
+    }
+
+    #[cfg(feature = "mysql")]
+    #[test]
+    fn normalize_timestamps() {
+        for (declared, reported) in [("TIMESTAMP", "timestamp"), ("DATETIME(3)", "datetime(3)")] {
+            assert_eq!(
+                normalize_type(declared),
+                normalize_type(reported),
+                "{}",
+                declared
+            );
+        }
+    }
+}
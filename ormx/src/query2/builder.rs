@@ -0,0 +1,199 @@
+use std::marker::PhantomData;
+
+use sqlx::database::HasArguments;
+use sqlx::query::{Map, QueryScalar};
+use sqlx::{Arguments, Database, Encode, Result, Type};
+
+use crate::query2::ConditionalQuery;
+use crate::Db;
+
+/// Assembles the SQL and the arguments of a conditional query at runtime.
+pub struct QueryBuilder<'q> {
+    sql: String,
+    arguments: <Db as HasArguments<'q>>::Arguments,
+    count: usize,
+}
+
+impl<'q> QueryBuilder<'q> {
+    pub fn new() -> Self {
+        QueryBuilder {
+            sql: String::new(),
+            arguments: Default::default(),
+            count: 0,
+        }
+    }
+
+    /// Appends a fragment of SQL, separated by a space.
+    pub fn push_sql(&mut self, sql: &str) {
+        if !self.sql.is_empty() {
+            self.sql.push(' ');
+        }
+        self.sql.push_str(sql);
+    }
+
+    /// Appends a placeholder and binds `value` to it.
+    pub fn push_arg<T>(&mut self, value: T)
+    where
+        T: 'q + Send + Encode<'q, Db> + Type<Db>,
+    {
+        let placeholder = self.placeholder();
+        self.push_sql(&placeholder);
+        self.arguments.add(value);
+    }
+
+    /// Appends `= ANY(<placeholder>)` and binds all elements of `list` as an array to it.
+    #[cfg(feature = "postgres")]
+    pub fn push_list<I>(&mut self, list: I)
+    where
+        I: IntoIterator,
+        Vec<I::Item>: 'q + Send + Encode<'q, Db> + Type<Db>,
+    {
+        let placeholder = self.placeholder();
+        self.push_sql(&format!("= ANY({})", placeholder));
+        self.arguments.add(list.into_iter().collect::<Vec<_>>());
+    }
+
+    /// Appends `IN (<placeholder>, ..)` and binds every element of `list` to its own placeholder.
+    /// Since `IN ()` is not valid, an empty list is compared using `IN (NULL)`, which never matches.
+    #[cfg(feature = "mysql")]
+    pub fn push_list<I>(&mut self, list: I)
+    where
+        I: IntoIterator,
+        I::Item: 'q + Send + Encode<'q, Db> + Type<Db>,
+    {
+        let mut placeholders = vec![];
+        for value in list {
+            placeholders.push(self.placeholder());
+            self.arguments.add(value);
+        }
+        match placeholders.is_empty() {
+            true => self.push_sql("IN (NULL)"),
+            false => self.push_sql(&format!("IN ({})", placeholders.join(", "))),
+        }
+    }
+
+    fn placeholder(&mut self) -> String {
+        self.count += 1;
+        #[cfg(feature = "postgres")]
+        return format!("${}", self.count);
+        #[cfg(feature = "mysql")]
+        return "?".to_owned();
+    }
+
+    pub fn build<M>(self, map: M) -> ConditionalQuery<'q, M> {
+        ConditionalQuery {
+            map,
+            sql: self.sql,
+            arguments: self.arguments,
+            count: self.count,
+        }
+    }
+}
+
+impl<'q> Default for QueryBuilder<'q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Records the type of an argument in `witness`, so that it can be used in the compile-time checks
+/// of a conditional query after the argument itself has been moved into a [QueryBuilder].
+#[doc(hidden)]
+pub fn bind<T>(_witness: &PhantomData<T>, value: T) -> T {
+    value
+}
+
+/// Like [bind], but records the type of the elements of `list`.
+#[doc(hidden)]
+pub fn bind_list<I: IntoIterator>(_witness: &PhantomData<I::Item>, list: I) -> I {
+    list
+}
+
+/// Produces a value of the type recorded in `witness`.
+/// The compile-time checks of conditional queries only need the type of their arguments, so they
+/// are placed within code which never runs, and this is never called.
+#[doc(hidden)]
+pub fn dummy<T>(_witness: &PhantomData<T>) -> T {
+    unreachable!("the checks of a conditional query are never executed")
+}
+
+/// Produces `NULL` of the type recorded in `witness`.
+/// The query mapping the rows of a conditional query is built with these arguments, which are
+/// replaced by the arguments of the assembled query when it is executed.
+#[doc(hidden)]
+pub fn null<T>(_witness: &PhantomData<T>) -> Option<T> {
+    None
+}
+
+/// Like [null], but produces an array of the elements recorded in `witness`.
+#[doc(hidden)]
+pub fn null_list<T>(_witness: &PhantomData<T>) -> Option<Vec<T>> {
+    None
+}
+
+/// A query mapping rows to [Mapping::Output].
+#[doc(hidden)]
+pub trait Mapping {
+    type Output;
+}
+
+impl<'q, F, O, A> Mapping for Map<'q, Db, F, A>
+where
+    F: FnMut(<Db as Database>::Row) -> Result<O>,
+{
+    type Output = O;
+}
+
+impl<'q, O, A> Mapping for QueryScalar<'q, Db, O, A> {
+    type Output = O;
+}
+
+/// Fails to compile unless the query checking a branch of a conditional query returns the same type
+/// as the query mapping its rows.
+#[doc(hidden)]
+pub fn same_output<M, C>(_mapping: &M, _check: C)
+where
+    M: Mapping,
+    C: Mapping<Output = M::Output>,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QueryBuilder;
+
+    fn list(values: Vec<i32>) -> QueryBuilder<'static> {
+        let mut query = QueryBuilder::new();
+        query.push_sql("SELECT 1 WHERE 1");
+        query.push_list(values);
+        query.push_sql("AND 2 =");
+        query.push_arg(2);
+        query
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn list_as_array() {
+        for values in [vec![], vec![1, 2, 3]] {
+            let query = list(values);
+            assert_eq!(query.sql, "SELECT 1 WHERE 1 = ANY($1) AND 2 = $2");
+            assert_eq!(query.count, 2);
+        }
+    }
+
+    #[cfg(feature = "mysql")]
+    #[test]
+    fn list_of_placeholders() {
+        let query = list(vec![1, 2, 3]);
+        assert_eq!(query.sql, "SELECT 1 WHERE 1 IN (?, ?, ?) AND 2 = ?");
+        assert_eq!(query.count, 4);
+    }
+
+    #[cfg(feature = "mysql")]
+    #[test]
+    fn empty_list() {
+        let query = list(vec![]);
+        assert_eq!(query.sql, "SELECT 1 WHERE 1 IN (NULL) AND 2 = ?");
+        assert_eq!(query.count, 1);
+    }
+}
@@ -1,343 +1,292 @@
-#[doc(hidden)]
-#[rustfmt::skip]
-pub mod map;
-#[cfg(feature = "mysql")]
-mod mysql;
-#[cfg(feature = "postgres")]
-mod postgres;
+use futures::channel::mpsc::{self, Sender};
+use futures::future::{self, Future, FutureExt};
+use futures::stream::{self, BoxStream, StreamExt};
+use futures::SinkExt;
+use sqlx::database::HasArguments;
+use sqlx::query::{Map, Query, QueryAs, QueryScalar};
+use sqlx::{Database, Error, Executor, FromRow, IntoArguments, Result};
 
-/// An improved version of `sqlx::query_as!`.
-///
-/// # Syntax
-/// The syntax of `conditional_query_as!` differs from the original `sqlx::query_as!`.
-/// Formally, it accepts this syntax:
-/// ```rust,ignore
-/// conditional_query_as!(
-///     PATH,
-///     (
-///         LITERAL |
-///         ?(EXPRESSION) |
-///         PATTERN = EXPRESSION => { (LITERAL | ?(EXPRESSION))* }
-///     )*
-/// )
-/// ```
-/// Arguments are now provided inline with the query like this:
-/// ```rust,ignore
-/// let user_id = Some(2);
-/// conditional_query_as!(
-///     User,
-///     "SELECT * FROM users WHERE user_id =" ?(user_id.unwrap())
-/// );
-/// ```
-/// Also, `conditional_query_as!` can parse multiple string literals like this:
-/// ```rust,ignore
-/// conditional_query_as!(
-///     User,
-///     "SELECT * FROM users"
-///     "WHERE user_id =" ?(user_id)
-///     "AND first_name =" ?(first_name)
-/// );
-/// ```
-///
-/// # Conditions
-/// `conditional_query_as!` can be used to have queries depend on a condition during runtime.
-/// This is achieved by checking the correctness of all possible queries at compile time.
-/// The syntax for a condition is
-/// ```ignore
-/// PATTERN = EXPRESSION => { (LITERAL | ?(EXPRESSION))* }
-/// ```
-/// Please note that conditions can't be nested right now.
-/// Also, the number of conditions per query is currently limited to 5.
-///
-/// Example:
-/// ```rust,ignore
-/// let limit = Some(10);
-/// conditional_query_as!(
-///     User,
-///     "SELECT * FROM users"
-///     Some(l) = limit => {
-///         "LIMIT" ?(l)
-///     }
-/// );
-/// ```
+use crate::Db;
+pub use builder::*;
+use rewrite::Rewrite;
+
+mod builder;
+mod rewrite;
+
+/// A query built by [conditional_query_as!](macro.conditional_query_as.html),
+/// [conditional_query!](macro.conditional_query.html),
+/// [conditional_query_scalar!](macro.conditional_query_scalar.html) or
+/// [conditional_execute!](macro.conditional_execute.html).
 ///
-#[macro_export]
-macro_rules! conditional_query_as {
-    ( $($t:tt)* ) => {
-        $crate::__conditional_query_as_impl!($($t)*)
-    };
+/// The SQL of the query is assembled at runtime, and executed using the query mapping its rows.
+pub struct ConditionalQuery<'q, M> {
+    map: M,
+    sql: String,
+    arguments: <Db as HasArguments<'q>>::Arguments,
+    count: usize,
 }
 
-#[doc(hidden)]
-#[macro_export]
-macro_rules! __build_query {
-    // --- @out_branch ---
-    // Build one branch
-    (
-        @out_branch
-        $out:path,
-        $variant:ident;
-        $appf:pat = $apef:expr $(, $appo:pat = $apeo:expr)*;
-        $($aq:literal),*;
-        $($ae:expr),*;
-    ) => {
-        if let $appf = $apef {
-            $crate::__build_query! {
-                @out_branch
-                $out,
-                $variant;
-                $($appo = $apeo),*;
-                $($aq),*;
-                $($ae),*;
-            }
-        }
-    };
-    (
-        @out_branch
-        $out:path,
-        $variant:ident;
-        ;
-        $($aq:literal),*;
-        $($ae:expr),*;
-    ) => {
-        break $variant($crate::__sqlx_query_as!(
-            $out,
-            $($aq),*;
-            $($ae),*
-        ));
-    };
+/// The maximum number of parameters a single statement can bind. Both the postgres and the mysql
+/// protocol transmit the number of parameters as a 16 bit integer.
+const MAX_ARGUMENTS: usize = u16::MAX as usize;
 
-    // --- @out ---
-    // Build branches, one by one
-    ( @out $out:path, $($v:ident),*; ; ) => {};
-    (
-        @out
-        $out:path,
-        $vf:ident $(, $vo:ident)*;
-        (
-            $($appf:pat = $apef:expr),*;
-            $($aqf:literal),*;
-            $($aef:expr),*;
-        )
-        $(, (
-            $($appo:pat = $apeo:expr),*;
-            $($aqo:literal),*;
-            $($aeo:expr),*;
-        ))*;
-    ) => {{
-        $crate::__build_query!(
-            @out_branch
-            $out,
-            $vf;
-            $($appf = $apef),*;
-            $($aqf),*;
-            $($aef),*;
-        );
-        $crate::__build_query!(
-            @out
-            $out,
-            $($vo),*;
-            $((
-                $($appo = $apeo),*;
-                $($aqo),*;
-                $($aeo),*;
-            )),*;
-        );
-    }};
+impl<'q, M> ConditionalQuery<'q, M> {
+    /// Returns the SQL of this query.
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
 
-    // -- Main Macro --
-    (
-        $((
-            $($bp:literal),*;
-            $($app:pat = $ape:expr),*;
-            $($aq:literal),*;
-            $($ae:expr),*;
-        )),*;
-        $out:path,
-    ) => {{
-        #[allow(unreachable_code)]
-        // TODO: remove this once sqlx 0.4 is out
-        #[allow(clippy::toplevel_ref_arg)]
-        loop {
-            $crate::__import_conditional_map!($(($($app=$ape),*;$($aq),*;$($ae),*;)),*);
-            $crate::__build_query!(
-                @out
-                $out,
-                _1, _2, _3, _4, _5, _6, _7, _8, _9, _10, _11, _12, _13, _14, _15, _16;
-                $((
-                    $($app = $ape),*;
-                    $($aq),*;
-                    $($ae),*;
-                )),*;
-            );
-            unreachable!();
-        }
-    }};
-    // When encountering a query string, add it to all branches
-    (
-        $((
-            $($bp:literal),*;
-            $($app:pat = $ape:expr),*;
-            $($aq:literal),*;
-            $($ae:expr),*;
-        )),*;
-        $out:path,
-        $q:literal $($t:tt)*
-    ) => {
-        $crate::__build_query! {
-            $((
-                $($bp),*;
-                $($app = $ape),*;
-                $($aq,)* $q;
-                $($ae),*;
-            )),*;
-            $out,
-            $($t)*
-        }
-    };
-    // When encountering a query parameter, add it to all branches
-    (
-        $((
-            $bpf:literal $(, $bpo:literal)*;
-            $($app:pat = $ape:expr),*;
-            $($aq:literal),*;
-            $($ae:expr),*;
-        )),*;
-        $out:path,
-        ?($e:expr) $($t:tt)*
-    ) => {
-        $crate::__build_query! {
-            $((
-                $($bpo),*;
-                $($app = $ape),*;
-                $($aq,)* $bpf;
-                $($ae,)* $e;
-            )),*;
-            $out,
-            $($t)*
-        }
-    };
-    // When encountering a branch, clone all existing branches, adding the new one.
-    (
-        $((
-            $($bp:literal),*;
-            $($app:pat = $ape:expr),*;
-            $($aq:literal),*;
-            $($ae:expr),*;
-        )),*;
-        $out:path,
-        $bpp:pat = $bpe:expr => {
-            $($bt:tt)*
+    /// Splits the query into its mapping, its SQL and its arguments.
+    /// Fails if the query binds more than [MAX_ARGUMENTS] parameters, which can happen when binding
+    /// large lists on mysql.
+    fn into_parts(self) -> Result<(M, String, <Db as HasArguments<'q>>::Arguments)> {
+        if self.count > MAX_ARGUMENTS {
+            return Err(Error::Protocol(format!(
+                "conditional query binds {} parameters, but at most {} are supported",
+                self.count, MAX_ARGUMENTS
+            )));
         }
-        $($t:tt)*
-    ) => {
-        $crate::__push_fragments!(
-            // all existing branches
-            $((
-                $($bp),*;
-                $($app = $ape,)* $bpp = $bpe;
-                $($aq),*;
-                $($ae),*;
-            )),*;
-            // out path
-            $out,
-            // content of new branch
-            { $($bt)* };
+        Ok((self.map, self.sql, self.arguments))
+    }
+}
 
-            // copy of old branches
-            [$(, (
-                $($bp),*;
-                $($app = $ape),*;
-                $($aq),*;
-                $($ae),*;
-            ))*];
-            // remaining tokens
-            $($t)*
-        )
-    };
+/// Streams the rows sent by `forward`.
+///
+/// The rows are fetched by an executor borrowing the SQL, which is owned by the query. Since a
+/// stream can't borrow from itself, the rows are fetched by a future owning the SQL instead, and
+/// passed to the stream through a channel. The future is polled by the returned stream, so nothing
+/// is spawned.
+fn forward<'e, T, F, Fut>(forward: F) -> BoxStream<'e, T>
+where
+    T: 'e + Send,
+    F: FnOnce(Sender<T>) -> Fut,
+    Fut: 'e + Send + Future<Output = ()>,
+{
+    let (sender, receiver) = mpsc::channel(0);
+    let forward = forward(sender)
+        .into_stream()
+        .filter_map(|()| future::ready(None));
+    stream::select(receiver, forward).boxed()
 }
 
-// Push multiple branch predicates, query fragments and parameters to all branches.
-#[doc(hidden)]
-#[macro_export]
-macro_rules! __push_fragments {
-    // We are done!
-    (
-        $(($ ($t:tt)* )),*;
-        $out:path,
-        {};
-        [$($y:tt)*];
-        $($x:tt)*
-    ) => {
-        $crate::__build_query! {
-            $(( $($t)* )),* $($y)*;
-            $out,
-            $($x)*
-        }
-    };
-    // When we encounter a literal, add it to all branches.
-    (
-        $((
-            $($bp:literal),*;
-            $($app:pat = $ape:expr),*;
-            $($aq:literal),*;
-            $($ae:expr),*;
-        )),*;
-        $out:path,
-        { $qf:literal $($bo:tt)* }; // <- the literal
-        [$($y:tt)*];
-        $($x:tt)*
-    ) => {
-        $crate::__push_fragments!{
-            $((
-                $($bp),*;
-                $($app = $ape),*;
-                $($aq,)* $qf;
-                $($ae),*;
-            )),*;
-            $out,
-            { $($bo)* };
-            [$($y)*];
-            $($x)*
+async fn send_all<T>(mut rows: BoxStream<'_, T>, mut sender: Sender<T>) {
+    while let Some(row) = rows.next().await {
+        // the stream has been dropped
+        if sender.send(row).await.is_err() {
+            break;
         }
-    };
-    // When we encounter an argument, add it to all branches.
-    (
-        $((
-            $bpf:literal $(, $bpo:literal)*;
-            $($app:pat = $ape:expr),*;
-            $($aq:literal),*;
-            $($ae:expr),*;
-        )),*;
-        $out:path,
-        { ?($qf:expr) $($bo:tt)* }; // <- the argument
-        [$($y:tt)*];
-        $($x:tt)*
-    ) => {
-        $crate::__push_fragments! {
-            $((
-                $($bpo),*;
-                $($app = $ape),*;
-                $($aq,)* $bpf;
-                $($ae,)* $qf;
-            )),*;
-            $out,
-            { $($bo)* };
-            [$($y)*];
-            $($x)*
-        }
-    };
+    }
+}
+
+impl<'q, F, O, A> ConditionalQuery<'q, Map<'q, Db, F, A>>
+where
+    F: FnMut(<Db as Database>::Row) -> Result<O> + Send,
+    O: Send + Unpin,
+    A: 'q + Send + IntoArguments<'q, Db>,
+{
+    pub fn fetch<'e, 'c: 'e, E>(self, executor: E) -> BoxStream<'e, Result<O>>
+    where
+        'q: 'c,
+        E: 'e + Executor<'c, Database = Db>,
+        F: 'e,
+        O: 'e,
+    {
+        let (map, sql, arguments) = match self.into_parts() {
+            Ok(parts) => parts,
+            Err(err) => return stream::once(async { Err(err) }).boxed(),
+        };
+        forward(move |sender| async move {
+            let rewrite = Rewrite::new(executor, &sql, arguments);
+            send_all(map.fetch(rewrite), sender).await
+        })
+    }
+
+    pub async fn fetch_all<'e, 'c: 'e, E>(self, executor: E) -> Result<Vec<O>>
+    where
+        'q: 'c,
+        E: 'e + Executor<'c, Database = Db>,
+        F: 'e,
+        O: 'e,
+    {
+        let (map, sql, arguments) = self.into_parts()?;
+        map.fetch_all(Rewrite::new(executor, &sql, arguments)).await
+    }
+
+    pub async fn fetch_one<'e, 'c: 'e, E>(self, executor: E) -> Result<O>
+    where
+        'q: 'c,
+        E: 'e + Executor<'c, Database = Db>,
+        F: 'e,
+        O: 'e,
+    {
+        let (map, sql, arguments) = self.into_parts()?;
+        map.fetch_one(Rewrite::new(executor, &sql, arguments)).await
+    }
+
+    pub async fn fetch_optional<'e, 'c: 'e, E>(self, executor: E) -> Result<Option<O>>
+    where
+        'q: 'c,
+        E: 'e + Executor<'c, Database = Db>,
+        F: 'e,
+        O: 'e,
+    {
+        let (map, sql, arguments) = self.into_parts()?;
+        map.fetch_optional(Rewrite::new(executor, &sql, arguments))
+            .await
+    }
 }
 
-// Do the actual call to sqlx::query_as!
-#[doc(hidden)]
-#[macro_export]
-macro_rules! __sqlx_query_as {
-    (
-        $out:path,
-        $f:literal $(, $o:literal)*;
-        $($e:expr),*
-    ) => {
-        sqlx::query_as!($out, $f $(+ " " + $o)*, $($e),*)
-    };
+impl<'q, O, A> ConditionalQuery<'q, QueryAs<'q, Db, O, A>>
+where
+    O: Send + Unpin + for<'r> FromRow<'r, <Db as Database>::Row>,
+    A: 'q + Send + IntoArguments<'q, Db>,
+{
+    pub fn fetch<'e, 'c: 'e, E>(self, executor: E) -> BoxStream<'e, Result<O>>
+    where
+        'q: 'c,
+        E: 'e + Executor<'c, Database = Db>,
+        A: 'e,
+        O: 'e,
+    {
+        let (map, sql, arguments) = match self.into_parts() {
+            Ok(parts) => parts,
+            Err(err) => return stream::once(async { Err(err) }).boxed(),
+        };
+        forward(move |sender| async move {
+            let rewrite = Rewrite::new(executor, &sql, arguments);
+            send_all(map.fetch(rewrite), sender).await
+        })
+    }
+
+    pub async fn fetch_all<'e, 'c: 'e, E>(self, executor: E) -> Result<Vec<O>>
+    where
+        'q: 'c,
+        E: 'e + Executor<'c, Database = Db>,
+        A: 'e,
+        O: 'e,
+    {
+        let (map, sql, arguments) = self.into_parts()?;
+        map.fetch_all(Rewrite::new(executor, &sql, arguments)).await
+    }
+
+    pub async fn fetch_one<'e, 'c: 'e, E>(self, executor: E) -> Result<O>
+    where
+        'q: 'c,
+        E: 'e + Executor<'c, Database = Db>,
+        A: 'e,
+        O: 'e,
+    {
+        let (map, sql, arguments) = self.into_parts()?;
+        map.fetch_one(Rewrite::new(executor, &sql, arguments)).await
+    }
+
+    pub async fn fetch_optional<'e, 'c: 'e, E>(self, executor: E) -> Result<Option<O>>
+    where
+        'q: 'c,
+        E: 'e + Executor<'c, Database = Db>,
+        A: 'e,
+        O: 'e,
+    {
+        let (map, sql, arguments) = self.into_parts()?;
+        map.fetch_optional(Rewrite::new(executor, &sql, arguments))
+            .await
+    }
+}
+
+impl<'q, O, A> ConditionalQuery<'q, QueryScalar<'q, Db, O, A>>
+where
+    O: Send + Unpin,
+    A: 'q + Send + IntoArguments<'q, Db>,
+    (O,): Send + Unpin + for<'r> FromRow<'r, <Db as Database>::Row>,
+{
+    pub fn fetch<'e, 'c: 'e, E>(self, executor: E) -> BoxStream<'e, Result<O>>
+    where
+        'q: 'c,
+        E: 'e + Executor<'c, Database = Db>,
+        A: 'e,
+        O: 'e,
+    {
+        let (map, sql, arguments) = match self.into_parts() {
+            Ok(parts) => parts,
+            Err(err) => return stream::once(async { Err(err) }).boxed(),
+        };
+        forward(move |sender| async move {
+            let rewrite = Rewrite::new(executor, &sql, arguments);
+            send_all(map.fetch(rewrite), sender).await
+        })
+    }
+
+    pub async fn fetch_all<'e, 'c: 'e, E>(self, executor: E) -> Result<Vec<O>>
+    where
+        'q: 'c,
+        E: 'e + Executor<'c, Database = Db>,
+        A: 'e,
+        O: 'e,
+    {
+        let (map, sql, arguments) = self.into_parts()?;
+        map.fetch_all(Rewrite::new(executor, &sql, arguments)).await
+    }
+
+    pub async fn fetch_one<'e, 'c: 'e, E>(self, executor: E) -> Result<O>
+    where
+        'q: 'c,
+        E: 'e + Executor<'c, Database = Db>,
+        A: 'e,
+        O: 'e,
+    {
+        let (map, sql, arguments) = self.into_parts()?;
+        map.fetch_one(Rewrite::new(executor, &sql, arguments)).await
+    }
+
+    pub async fn fetch_optional<'e, 'c: 'e, E>(self, executor: E) -> Result<Option<O>>
+    where
+        'q: 'c,
+        E: 'e + Executor<'c, Database = Db>,
+        A: 'e,
+        O: 'e,
+    {
+        let (map, sql, arguments) = self.into_parts()?;
+        map.fetch_optional(Rewrite::new(executor, &sql, arguments))
+            .await
+    }
+}
+
+impl<'q, A> ConditionalQuery<'q, Query<'q, Db, A>>
+where
+    A: 'q + Send + IntoArguments<'q, Db>,
+{
+    pub async fn execute<'e, 'c: 'e, E>(self, executor: E) -> Result<<Db as Database>::QueryResult>
+    where
+        'q: 'c,
+        E: 'e + Executor<'c, Database = Db>,
+    {
+        let (map, sql, arguments) = self.into_parts()?;
+        map.execute(Rewrite::new(executor, &sql, arguments)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Error;
+
+    use super::{QueryBuilder, MAX_ARGUMENTS};
+
+    fn query(count: usize) -> QueryBuilder<'static> {
+        let mut query = QueryBuilder::new();
+        query.push_sql("SELECT 1 WHERE 1 IN (");
+        for idx in 0..count {
+            query.push_arg(idx as i32);
+        }
+        query.push_sql(")");
+        query
+    }
+
+    #[test]
+    fn bind_max_arguments() {
+        assert!(query(MAX_ARGUMENTS).build(()).into_parts().is_ok());
+        match query(MAX_ARGUMENTS + 1).build(()).into_parts() {
+            Err(Error::Protocol(message)) => assert!(message.contains("65536"), "{}", message),
+            _ => panic!("expected a protocol error"),
+        }
+    }
 }
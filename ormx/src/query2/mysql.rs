@@ -1,23 +0,0 @@
-#[doc(hidden)]
-#[macro_export]
-macro_rules! __conditional_query_as_impl {
-    ( $($t:tt)* ) => {
-        $crate::__build_query!(
-            // 100 parameters should hopefully be enough ._.
-            (
-                "?", "?", "?", "?", "?", "?", "?", "?", "?", "?",
-                "?", "?", "?", "?", "?", "?", "?", "?", "?", "?",
-                "?", "?", "?", "?", "?", "?", "?", "?", "?", "?",
-                "?", "?", "?", "?", "?", "?", "?", "?", "?", "?",
-                "?", "?", "?", "?", "?", "?", "?", "?", "?", "?",
-                "?", "?", "?", "?", "?", "?", "?", "?", "?", "?",
-                "?", "?", "?", "?", "?", "?", "?", "?", "?", "?",
-                "?", "?", "?", "?", "?", "?", "?", "?", "?", "?",
-                "?", "?", "?", "?", "?", "?", "?", "?", "?", "?",
-                "?", "?", "?", "?", "?", "?", "?", "?", "?", "?";
-                ;;;
-            );
-            $($t)*
-        )
-    };
-}
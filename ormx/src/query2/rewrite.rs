@@ -0,0 +1,92 @@
+use std::fmt::{self, Debug, Formatter};
+
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use sqlx::database::{HasArguments, HasStatement};
+use sqlx::{Database, Describe, Either, Error, Execute, Executor};
+
+use crate::Db;
+
+/// Executor which runs the SQL assembled at runtime instead of the query it is given.
+///
+/// The rows of a conditional query are mapped by a `sqlx::query::Map`, which can only be executed
+/// with the query it was created from. Passing this executor to it instead replaces the query while
+/// keeping its mapping.
+pub(crate) struct Rewrite<'s, 'q, E> {
+    executor: E,
+    sql: &'s str,
+    arguments: <Db as HasArguments<'q>>::Arguments,
+}
+
+impl<'s, 'q, E> Rewrite<'s, 'q, E> {
+    pub(crate) fn new(
+        executor: E,
+        sql: &'s str,
+        arguments: <Db as HasArguments<'q>>::Arguments,
+    ) -> Self {
+        Rewrite {
+            executor,
+            sql,
+            arguments,
+        }
+    }
+}
+
+impl<'s, 'q, E: Debug> Debug for Rewrite<'s, 'q, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Rewrite")
+            .field("executor", &self.executor)
+            .field("sql", &self.sql)
+            .finish()
+    }
+}
+
+// The SQL is borrowed for `'s`, which may be shorter than the lifetime `'c` of the executor.
+impl<'c: 's, 's, 'a: 's, E> Executor<'s> for Rewrite<'s, 'a, E>
+where
+    E: Executor<'c, Database = Db>,
+{
+    type Database = Db;
+
+    fn fetch_many<'e, 'q: 'e, Q>(
+        self,
+        _query: Q,
+    ) -> BoxStream<'e, Result<Either<<Db as Database>::QueryResult, <Db as Database>::Row>, Error>>
+    where
+        's: 'e,
+        Q: 'q + Execute<'q, Db>,
+    {
+        let query = sqlx::query_with(self.sql, self.arguments);
+        self.executor.fetch_many(query)
+    }
+
+    fn fetch_optional<'e, 'q: 'e, Q>(
+        self,
+        _query: Q,
+    ) -> BoxFuture<'e, Result<Option<<Db as Database>::Row>, Error>>
+    where
+        's: 'e,
+        Q: 'q + Execute<'q, Db>,
+    {
+        let query = sqlx::query_with(self.sql, self.arguments);
+        self.executor.fetch_optional(query)
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [<Db as Database>::TypeInfo],
+    ) -> BoxFuture<'e, Result<<Db as HasStatement<'q>>::Statement, Error>>
+    where
+        's: 'e,
+    {
+        self.executor.prepare_with(sql, parameters)
+    }
+
+    fn describe<'e, 'q: 'e>(self, sql: &'q str) -> BoxFuture<'e, Result<Describe<Db>, Error>>
+    where
+        's: 'e,
+    {
+        self.executor.describe(sql)
+    }
+}
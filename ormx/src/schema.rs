@@ -0,0 +1,132 @@
+use futures::future::BoxFuture;
+use sqlx::{Database, Executor, Result};
+
+use crate::{Db, TableMeta};
+
+/// The schema of a table, implemented by `derive(Table)` for tables annotated with
+/// `#[ormx(ddl)]`.
+pub trait Schema: TableMeta {
+    /// Returns the `CREATE TABLE IF NOT EXISTS` statement for this table.
+    fn create_table_sql() -> String;
+
+    /// Returns the `CREATE INDEX IF NOT EXISTS` statements for the indexes of this table.
+    /// On mysql, indexes are created within `CREATE TABLE` instead.
+    fn create_indexes_sql() -> Vec<String>;
+
+    /// Returns the `COMMENT ON` statements for the doc comments of the struct and its fields.
+    /// On mysql, comments are defined within `CREATE TABLE` instead.
+    fn create_comments_sql() -> Vec<String>;
+
+    /// Returns the definition of every column, e.g. `email VARCHAR(128) NOT NULL`, in the order
+    /// of [TableMeta::COLUMNS].
+    fn column_definitions() -> Vec<String>;
+
+    /// Returns the statements adding every index to an existing table, in the order of
+    /// [TableMeta::INDEXES].
+    fn add_indexes_sql() -> Vec<String>;
+
+    /// Creates this table and its indexes, if they don't exist yet, and sets the comments.
+    fn create_table(db: &mut <Db as Database>::Connection) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            let sql = Self::create_table_sql();
+            (&mut *db).execute(sql.as_str()).await?;
+            for sql in Self::create_indexes_sql() {
+                (&mut *db).execute(sql.as_str()).await?;
+            }
+            for sql in Self::create_comments_sql() {
+                (&mut *db).execute(sql.as_str()).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// A Rust type with a corresponding SQL type, used to infer the types of columns in the schema of
+/// a table.
+///
+/// Fields of custom types either implement this trait or are annotated with
+/// `#[ormx(sql_type = ..)]`:
+/// ```rust,ignore
+/// impl ormx::SqlType for Role {
+///     const SQL_TYPE: &'static str = "user_role";
+/// }
+/// ```
+pub trait SqlType {
+    /// The SQL type of a column containing values of this type.
+    const SQL_TYPE: &'static str;
+}
+
+/// Whether the column may be NULL is decided by the table, so `Option<T>` maps to the type of `T`.
+impl<T: SqlType> SqlType for Option<T> {
+    const SQL_TYPE: &'static str = T::SQL_TYPE;
+}
+
+macro_rules! sql_types {
+    ($( $(#[$attr:meta])* $ty:ty => $sql_type:literal ),* $(,)?) => {
+        $(
+            $(#[$attr])*
+            impl SqlType for $ty {
+                const SQL_TYPE: &'static str = $sql_type;
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "postgres")]
+sql_types! {
+    bool => "BOOLEAN",
+    i16 => "SMALLINT",
+    i32 => "INTEGER",
+    i64 => "BIGINT",
+    f32 => "REAL",
+    f64 => "DOUBLE PRECISION",
+    String => "TEXT",
+    Vec<u8> => "BYTEA",
+    #[cfg(feature = "chrono")]
+    sqlx::types::chrono::NaiveDate => "DATE",
+    #[cfg(feature = "chrono")]
+    sqlx::types::chrono::NaiveTime => "TIME",
+    #[cfg(feature = "chrono")]
+    sqlx::types::chrono::NaiveDateTime => "TIMESTAMP",
+    #[cfg(feature = "chrono")]
+    sqlx::types::chrono::DateTime<sqlx::types::chrono::Utc> => "TIMESTAMPTZ",
+    #[cfg(feature = "uuid")]
+    sqlx::types::Uuid => "UUID",
+    #[cfg(feature = "json")]
+    serde_json::Value => "JSONB",
+}
+
+#[cfg(feature = "mysql")]
+sql_types! {
+    bool => "BOOLEAN",
+    i8 => "TINYINT",
+    i16 => "SMALLINT",
+    i32 => "INT",
+    i64 => "BIGINT",
+    u8 => "TINYINT UNSIGNED",
+    u16 => "SMALLINT UNSIGNED",
+    u32 => "INT UNSIGNED",
+    u64 => "BIGINT UNSIGNED",
+    f32 => "FLOAT",
+    f64 => "DOUBLE",
+    // TEXT columns can't be part of a unique constraint or an index without a prefix length
+    String => "VARCHAR(255)",
+    Vec<u8> => "BLOB",
+    #[cfg(feature = "chrono")]
+    sqlx::types::chrono::NaiveDate => "DATE",
+    #[cfg(feature = "chrono")]
+    sqlx::types::chrono::NaiveTime => "TIME",
+    #[cfg(feature = "chrono")]
+    sqlx::types::chrono::NaiveDateTime => "DATETIME",
+    #[cfg(feature = "chrono")]
+    sqlx::types::chrono::DateTime<sqlx::types::chrono::Utc> => "TIMESTAMP",
+    #[cfg(feature = "uuid")]
+    sqlx::types::Uuid => "BINARY(16)",
+    #[cfg(feature = "json")]
+    serde_json::Value => "JSON",
+}
+
+#[cfg(feature = "json")]
+impl<T> SqlType for sqlx::types::Json<T> {
+    const SQL_TYPE: &'static str = serde_json::Value::SQL_TYPE;
+}
@@ -0,0 +1,201 @@
+use std::fmt::{self, Display, Formatter};
+
+use sqlx::{Acquire, Result};
+
+use crate::introspect::{columns, indexes, same_name};
+use crate::migrate::normalize_type;
+use crate::{Db, IndexMeta, TableMeta};
+
+/// Compares the table `T` with the table in the database, returning every mismatch which would
+/// make the generated queries fail at runtime.
+///
+/// The types of the columns are compared if they are known, i.e. if the table is annotated with
+/// `#[ormx(ddl)]`, since they are not checked at compile time with the `runtime-queries` feature.
+/// Columns of the database which are not part of the table are reported if they are `NOT NULL`
+/// without a default value, since inserting rows fails then. Columns which should generate their
+/// values, but are no identity columns, are reported as well, since
+/// [alter_sync](crate::alter_sync) does not migrate them.
+/// ```rust,ignore
+/// let diff = ormx::verify_schema::<User>(&pool).await?;
+/// if !diff.is_empty() {
+///     panic!("{}", diff);
+/// }
+/// ```
+pub async fn verify_schema<'c, T: TableMeta>(
+    db: impl Acquire<'c, Database = Db>,
+) -> Result<SchemaDiff> {
+    let mut conn = db.acquire().await?;
+    let columns = columns(&mut conn, T::SCHEMA, T::TABLE).await?;
+    let indexes = indexes(&mut conn, T::SCHEMA, T::TABLE).await?;
+
+    let mut mismatches = vec![];
+    if columns.is_empty() {
+        mismatches.push(Mismatch::MissingTable);
+        return Ok(SchemaDiff {
+            table: T::TABLE,
+            mismatches,
+        });
+    }
+    for column in T::COLUMNS {
+        let actual = match columns.iter().find(|c| same_name(&c.name, column.name)) {
+            Some(actual) => actual,
+            None => {
+                mismatches.push(Mismatch::MissingColumn {
+                    column: column.name,
+                });
+                continue;
+            }
+        };
+        match column.sql_type {
+            Some(sql_type) if normalize_type(sql_type) != normalize_type(&actual.sql_type) => {
+                mismatches.push(Mismatch::TypeMismatch {
+                    column: column.name,
+                    expected: sql_type,
+                    actual: actual.sql_type.clone(),
+                })
+            }
+            _ => {}
+        }
+        match (column.nullable, actual.nullable) {
+            (false, true) => mismatches.push(Mismatch::MissingNotNull {
+                column: column.name,
+            }),
+            (true, false) => mismatches.push(Mismatch::UnexpectedNotNull {
+                column: column.name,
+            }),
+            _ => {}
+        }
+        // nullable columns default to NULL
+        let requires_default = column.default || column.auto_increment;
+        if requires_default && !actual.has_default && !actual.nullable {
+            mismatches.push(Mismatch::MissingDefault {
+                column: column.name,
+            });
+        } else if column.auto_increment && !actual.auto_increment {
+            mismatches.push(Mismatch::MissingAutoIncrement {
+                column: column.name,
+            });
+        }
+    }
+    for actual in &columns {
+        let known = T::COLUMNS
+            .iter()
+            .any(|column| same_name(&actual.name, column.name));
+        if !known && !actual.nullable && !actual.has_default {
+            mismatches.push(Mismatch::UnexpectedColumn {
+                column: actual.name.clone(),
+            });
+        }
+    }
+    for index in T::INDEXES {
+        if !indexes.iter().any(|actual| actual.covers(index)) {
+            mismatches.push(Mismatch::MissingIndex { index: *index });
+        }
+    }
+
+    Ok(SchemaDiff {
+        table: T::TABLE,
+        mismatches,
+    })
+}
+
+/// The differences between a table and the corresponding table in the database, returned by
+/// [verify_schema].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaDiff {
+    /// Name of the table.
+    pub table: &'static str,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl SchemaDiff {
+    /// Returns true if the table matches the database.
+    pub fn is_empty(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl Display for SchemaDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "table {}", self.table)?;
+        if self.is_empty() {
+            return write!(f, " matches the database");
+        }
+        write!(f, " does not match the database:")?;
+        for mismatch in &self.mismatches {
+            write!(f, "\n- {}", mismatch)?;
+        }
+        Ok(())
+    }
+}
+
+/// A single difference between a table and the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// The table does not exist.
+    MissingTable,
+    /// The column does not exist.
+    MissingColumn { column: &'static str },
+    /// The column of the database is not part of the table, but is `NOT NULL` without a default
+    /// value, so inserting rows fails.
+    UnexpectedColumn { column: String },
+    /// The type of the column differs from the type declared by the table.
+    TypeMismatch {
+        column: &'static str,
+        expected: &'static str,
+        actual: String,
+    },
+    /// The field is not an `Option`, but the column may be NULL.
+    MissingNotNull { column: &'static str },
+    /// The field is an `Option`, but the column is `NOT NULL`.
+    UnexpectedNotNull { column: &'static str },
+    /// The field is annotated with `#[ormx(default)]` or `#[ormx(auto_increment)]`, but the
+    /// column has no default value.
+    MissingDefault { column: &'static str },
+    /// The field is annotated with `#[ormx(auto_increment)]` or `#[ormx(identity = ..)]`, but the
+    /// column is no identity column (e.g. a `SERIAL` column), or on mysql not `AUTO_INCREMENT`.
+    /// [alter_sync](crate::alter_sync) does not migrate such columns.
+    MissingAutoIncrement { column: &'static str },
+    /// The index does not exist, or is not unique.
+    MissingIndex { index: IndexMeta },
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::MissingTable => write!(f, "the table does not exist"),
+            Mismatch::MissingColumn { column } => write!(f, "column {} does not exist", column),
+            Mismatch::UnexpectedColumn { column } => write!(
+                f,
+                "column {} is NOT NULL without a default value, but not part of the table",
+                column
+            ),
+            Mismatch::TypeMismatch {
+                column,
+                expected,
+                actual,
+            } => write!(f, "column {} is {}, not {}", column, actual, expected),
+            Mismatch::MissingNotNull { column } => write!(f, "column {} may be NULL", column),
+            Mismatch::UnexpectedNotNull { column } => {
+                write!(
+                    f,
+                    "column {} is NOT NULL, but its field is optional",
+                    column
+                )
+            }
+            Mismatch::MissingDefault { column } => {
+                write!(f, "column {} has no default value", column)
+            }
+            Mismatch::MissingAutoIncrement { column } => {
+                write!(f, "column {} is no identity column", column)
+            }
+            Mismatch::MissingIndex { index } => write!(
+                f,
+                "{}index {} on ({}) does not exist",
+                if index.unique { "unique " } else { "" },
+                index.name,
+                index.columns.join(", ")
+            ),
+        }
+    }
+}
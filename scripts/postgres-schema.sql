@@ -2,7 +2,7 @@ CREATE TYPE user_role AS ENUM ('user', 'admin');
 
 CREATE TABLE users
 (
-    id         SERIAL PRIMARY KEY,
+    id         INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
     first_name VARCHAR(128) NOT NULL,
     last_name  VARCHAR(128) NOT NULL,
     email      VARCHAR(128) NOT NULL UNIQUE,